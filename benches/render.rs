@@ -0,0 +1,55 @@
+// Fixed-scene render benchmark, run with `cargo bench`. Renders a small random_scene with a
+// fixed seed a few times and reports the best and median wall time and camera paths per
+// second, plus a checksum of the image so a change that alters the output shows up too.
+
+use rusty_ray_tracer::*;
+use std::time::{Duration, Instant};
+
+const RUNS: usize = 3;
+
+fn main() {
+    let settings = RenderSettings {
+        image_width: 192,
+        fixed_height: Some(108),
+        samples_per_pixel: 8,
+        seed: Some(2021),
+        ..RenderSettings::default()
+    };
+
+    seed_random(2021);
+    let world = random_scene();
+    let cam = Camera::new(
+        Point3::new(13.0, 2.0, 3.0),
+        Point3::new(0.0, 0.0, 0.0),
+        Vec3::new(0.0, 1.0, 0.0),
+        20.0,
+        settings.pixel_aspect_ratio(),
+        0.1,
+        10.0,
+    );
+
+    let mut times: Vec<Duration> = Vec::with_capacity(RUNS);
+    let mut checksum = 0.0;
+    for _ in 0..RUNS {
+        let start = Instant::now();
+        let pixels = render(&world, &cam, &settings);
+        times.push(start.elapsed());
+        checksum = pixels.iter().map(|&pixel| luminance(pixel)).sum::<f64>();
+    }
+    times.sort();
+
+    let paths =
+        (settings.image_width * settings.image_height() * settings.samples_per_pixel) as f64;
+    let best = times[0].as_secs_f64();
+    let median = times[RUNS / 2].as_secs_f64();
+    println!(
+        "random_scene {}x{} at {} spp, {} runs",
+        settings.image_width,
+        settings.image_height(),
+        settings.samples_per_pixel,
+        RUNS
+    );
+    println!("best   {:.3}s  {:.0} paths/s", best, paths / best);
+    println!("median {:.3}s  {:.0} paths/s", median, paths / median);
+    println!("checksum {:.6}", checksum);
+}
@@ -0,0 +1,92 @@
+use crate::ray::Ray;
+use crate::vec3::{Point3, Vec3};
+
+// Axis-aligned bounding box, the corner with the smallest coordinates and the one with the
+// largest. A box with min above max on any axis is empty and never hit.
+
+#[derive(Copy, Clone)]
+pub struct Aabb {
+    min: Point3,
+    max: Point3,
+}
+
+impl Aabb {
+    // The corners may be given in any order
+    pub fn new(a: Point3, b: Point3) -> Aabb {
+        Aabb {
+            min: a.min(b),
+            max: a.max(b),
+        }
+    }
+
+    pub fn empty() -> Aabb {
+        Aabb {
+            min: Vec3::new(f64::INFINITY, f64::INFINITY, f64::INFINITY),
+            max: Vec3::new(f64::NEG_INFINITY, f64::NEG_INFINITY, f64::NEG_INFINITY),
+        }
+    }
+
+    pub fn min(&self) -> Point3 {
+        self.min
+    }
+
+    pub fn max(&self) -> Point3 {
+        self.max
+    }
+
+    // Smallest box holding both
+    pub fn surrounding(self, other: Aabb) -> Aabb {
+        Aabb {
+            min: self.min.min(other.min),
+            max: self.max.max(other.max),
+        }
+    }
+
+    pub fn centroid(&self) -> Point3 {
+        (self.min + self.max) * 0.5
+    }
+
+    // 0, 1 or 2 for whichever of x, y and z the box is longest along
+    pub fn longest_axis(&self) -> usize {
+        let size = self.max - self.min;
+        if size.x >= size.y && size.x >= size.z {
+            0
+        } else if size.y >= size.z {
+            1
+        } else {
+            2
+        }
+    }
+
+    // Slab test: the ray is inside the box where its intervals between each pair of planes
+    // overlap. Division by a zero direction component gives infinities, which ends up treating
+    // the ray as parallel to those planes.
+    pub fn hit(&self, r: Ray, t_min: f64, t_max: f64) -> bool {
+        let mut t_min = t_min;
+        let mut t_max = t_max;
+        for a in 0..3 {
+            let inv_d = 1.0 / axis(r.direction, a);
+            let mut t0 = (axis(self.min, a) - axis(r.origin, a)) * inv_d;
+            let mut t1 = (axis(self.max, a) - axis(r.origin, a)) * inv_d;
+            if inv_d < 0.0 {
+                std::mem::swap(&mut t0, &mut t1);
+            }
+            // max and min skip the NaN from a ray lying exactly in one of the planes
+            t_min = t_min.max(t0);
+            t_max = t_max.min(t1);
+            if t_max < t_min {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+// Component `axis` of `v`, 0 for x, 1 for y and 2 for z
+pub fn axis(v: Vec3, axis: usize) -> f64 {
+    match axis {
+        0 => v.x,
+        1 => v.y,
+        _ => v.z,
+    }
+}
@@ -0,0 +1,268 @@
+use crate::aabb::{axis, Aabb};
+use crate::hittable::{HitRecord, Hittable, HittableList};
+use crate::ray::Ray;
+use std::any::Any;
+use std::cmp::Ordering;
+use std::sync::Arc;
+
+// Bounding volume hierarchy: a binary tree of boxes over the scene, so a ray only tests the
+// objects whose boxes it passes through instead of every object in the list. Each node splits
+// its objects in half at the median along the longest axis of their centers. Hits are the same
+// as for a HittableList of the same objects, only found faster.
+
+pub struct BvhNode {
+    bbox: Aabb,
+    left: Arc<dyn Hittable>,
+    right: Arc<dyn Hittable>,
+    // Objects without a bounding box, which can only be kept at the root and tested on every
+    // ray. None of the built-in shapes need this.
+    unbounded: Vec<Arc<dyn Hittable>>,
+}
+
+impl BvhNode {
+    pub fn new(objects: Vec<Arc<dyn Hittable>>) -> BvhNode {
+        let (mut bounded, unbounded): (Vec<_>, Vec<_>) = objects
+            .into_iter()
+            .partition(|object| object.bounding_box().is_some());
+
+        let mut node = BvhNode::build(&mut bounded);
+        node.unbounded = unbounded;
+        node
+    }
+
+    fn build(objects: &mut [Arc<dyn Hittable>]) -> BvhNode {
+        let boxes = objects
+            .iter()
+            .filter_map(|object| object.bounding_box())
+            .fold(Aabb::empty(), Aabb::surrounding);
+
+        let (left, right): (Arc<dyn Hittable>, Arc<dyn Hittable>) = match objects.len() {
+            0 => (Arc::new(HittableList::new()), Arc::new(HittableList::new())),
+            1 => (objects[0].clone(), Arc::new(HittableList::new())),
+            2 => (objects[0].clone(), objects[1].clone()),
+            _ => {
+                let centers = objects
+                    .iter()
+                    .filter_map(|object| object.bounding_box())
+                    .map(|bbox| Aabb::new(bbox.centroid(), bbox.centroid()))
+                    .fold(Aabb::empty(), Aabb::surrounding);
+                let split_axis = centers.longest_axis();
+                let center = |object: &Arc<dyn Hittable>| match object.bounding_box() {
+                    Some(bbox) => axis(bbox.centroid(), split_axis),
+                    None => 0.0,
+                };
+
+                let mid = objects.len() / 2;
+                objects.select_nth_unstable_by(mid, |a, b| {
+                    center(a).partial_cmp(&center(b)).unwrap_or(Ordering::Equal)
+                });
+                let (low, high) = objects.split_at_mut(mid);
+                (
+                    Arc::new(BvhNode::build(low)),
+                    Arc::new(BvhNode::build(high)),
+                )
+            }
+        };
+
+        BvhNode {
+            bbox: boxes,
+            left,
+            right,
+            unbounded: Vec::new(),
+        }
+    }
+}
+
+impl Hittable for BvhNode {
+    fn hit(&self, r: Ray, t_min: f64, t_max: f64, rec: &mut HitRecord) -> bool {
+        let mut hit_anything = false;
+        let mut closest_so_far = t_max;
+
+        for object in &self.unbounded {
+            if let Some(temp_rec) = object.hit2(r, t_min, closest_so_far) {
+                hit_anything = true;
+                closest_so_far = temp_rec.t;
+                *rec = temp_rec;
+            }
+        }
+
+        if !self.bbox.hit(r, t_min, closest_so_far) {
+            return hit_anything;
+        }
+        if let Some(temp_rec) = self.left.hit2(r, t_min, closest_so_far) {
+            hit_anything = true;
+            closest_so_far = temp_rec.t;
+            *rec = temp_rec;
+        }
+        if let Some(temp_rec) = self.right.hit2(r, t_min, closest_so_far) {
+            hit_anything = true;
+            *rec = temp_rec;
+        }
+
+        hit_anything
+    }
+
+    fn hit_any(&self, r: Ray, t_min: f64, t_max: f64) -> bool {
+        if self
+            .unbounded
+            .iter()
+            .any(|object| object.hit_any(r, t_min, t_max))
+        {
+            return true;
+        }
+        self.bbox.hit(r, t_min, t_max)
+            && (self.left.hit_any(r, t_min, t_max) || self.right.hit_any(r, t_min, t_max))
+    }
+
+    // None when anything in the tree is unbounded
+    fn bounding_box(&self) -> Option<Aabb> {
+        if self.unbounded.is_empty() {
+            Some(self.bbox)
+        } else {
+            None
+        }
+    }
+
+    fn clone_box(&self) -> Arc<dyn Hittable> {
+        Arc::new(BvhNode {
+            bbox: self.bbox,
+            left: self.left.clone_box(),
+            right: self.right.clone_box(),
+            unbounded: self
+                .unbounded
+                .iter()
+                .map(|object| object.clone_box())
+                .collect(),
+        })
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::Lambertian;
+    use crate::sphere::Sphere;
+    use crate::vec3::{Color, Point3, Vec3};
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    // The plane y = -1, which has no bounding box
+    struct Floor;
+
+    impl Hittable for Floor {
+        fn hit(&self, r: Ray, t_min: f64, t_max: f64, rec: &mut HitRecord) -> bool {
+            let t = (-1.0 - r.origin.y) / r.direction.y;
+            if !(t >= t_min && t <= t_max) {
+                return false;
+            }
+            rec.t = t;
+            rec.p = r.at(t);
+            rec.set_face_normal(r, Vec3::new(0.0, 1.0, 0.0));
+            true
+        }
+
+        fn clone_box(&self) -> Arc<dyn Hittable> {
+            Arc::new(Floor)
+        }
+
+        fn as_any(&self) -> &dyn Any {
+            self
+        }
+    }
+
+    fn sphere_field(rng: &mut StdRng, count: usize) -> Vec<Arc<dyn Hittable>> {
+        let material = Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+        (0..count)
+            .map(|_| {
+                let center = Point3::new(
+                    rng.gen_range(-10.0..10.0),
+                    rng.gen_range(-10.0..10.0),
+                    rng.gen_range(-10.0..10.0),
+                );
+                let radius = rng.gen_range(0.2..2.0);
+                Arc::new(Sphere::new(center, radius, material.clone())) as Arc<dyn Hittable>
+            })
+            .collect()
+    }
+
+    // Fires the same rays at a BVH and a plain list of `objects`, expecting identical hits
+    fn assert_same_hits(objects: Vec<Arc<dyn Hittable>>, rng: &mut StdRng) {
+        let mut list = HittableList::new();
+        for object in &objects {
+            list.add(object.clone());
+        }
+        let bvh = BvhNode::new(objects);
+
+        for _ in 0..500 {
+            let origin = Point3::new(
+                rng.gen_range(-15.0..15.0),
+                rng.gen_range(-15.0..15.0),
+                rng.gen_range(-15.0..15.0),
+            );
+            let r = Ray::new(origin, Vec3::random_unit_vector_from(rng));
+            match (
+                bvh.hit2(r, 0.001, f64::INFINITY),
+                list.hit2(r, 0.001, f64::INFINITY),
+            ) {
+                (Some(a), Some(b)) => {
+                    assert_eq!(a.t, b.t);
+                    assert_eq!(
+                        (a.normal.x, a.normal.y, a.normal.z),
+                        (b.normal.x, b.normal.y, b.normal.z)
+                    );
+                }
+                (None, None) => {}
+                _ => panic!("the BVH and the list disagree on whether a ray hits"),
+            }
+            assert_eq!(
+                bvh.hit_any(r, 0.001, f64::INFINITY),
+                list.hit_any(r, 0.001, f64::INFINITY)
+            );
+        }
+    }
+
+    #[test]
+    fn matches_the_list_on_a_sphere_field() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let objects = sphere_field(&mut rng, 60);
+        assert_same_hits(objects, &mut rng);
+    }
+
+    #[test]
+    fn matches_the_list_with_few_objects() {
+        let mut rng = StdRng::seed_from_u64(2);
+        for count in 0..3 {
+            let objects = sphere_field(&mut rng, count);
+            assert_same_hits(objects, &mut rng);
+        }
+        assert!(BvhNode::new(Vec::new())
+            .hit2(
+                Ray::new(Point3::default(), Vec3::new(0.0, 0.0, -1.0)),
+                0.0,
+                1e9
+            )
+            .is_none());
+    }
+
+    #[test]
+    fn unbounded_objects_stay_at_the_root() {
+        let mut rng = StdRng::seed_from_u64(3);
+        let mut objects = sphere_field(&mut rng, 20);
+        objects.push(Arc::new(Floor));
+        let bvh = BvhNode::new(objects.clone());
+        assert_eq!(bvh.unbounded.len(), 1);
+        assert!(bvh.bounding_box().is_none());
+
+        // Straight down, well clear of the spheres, ends on the floor
+        let down = Ray::new(Point3::new(20.0, 5.0, 20.0), Vec3::new(0.0, -1.0, 0.0));
+        assert_eq!(
+            bvh.hit2(down, 0.001, f64::INFINITY).map(|rec| rec.t),
+            Some(6.0)
+        );
+        assert_same_hits(objects, &mut rng);
+    }
+}
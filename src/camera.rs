@@ -0,0 +1,400 @@
+use crate::aabb::Aabb;
+use crate::error::Error;
+use crate::ray::Ray;
+use crate::utility::degrees_to_radians;
+use crate::vec3::{cross, dot, Point3, Vec3};
+
+// How image coordinates are turned into ray directions
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum Projection {
+    // Regular thin-lens perspective camera
+    Perspective,
+    // Full 360 degree equirectangular panorama, s spans longitude and t spans latitude
+    Equirectangular,
+}
+
+// Physical lens description for Camera::from_lens. Lengths are in millimetres, and the scene is
+// taken to be in metres when turning the f-number into a lens opening.
+
+#[derive(Copy, Clone)]
+pub struct Lens {
+    pub focal_length_mm: f64,
+    // Width of the film or sensor, 36 for full frame
+    pub sensor_width_mm: f64,
+    // None is a pinhole, so everything is in focus
+    pub f_number: Option<f64>,
+}
+
+impl Lens {
+    pub fn new(focal_length_mm: f64, sensor_width_mm: f64) -> Lens {
+        Lens {
+            focal_length_mm,
+            sensor_width_mm,
+            f_number: None,
+        }
+    }
+
+    pub fn with_f_number(mut self, f_number: f64) -> Lens {
+        self.f_number = Some(f_number);
+        self
+    }
+
+    // The sensor width spans the horizontal field of view, so the vertical one is
+    // 2 * atan(sensor_width / (2 * focal_length * aspect_ratio)), in degrees
+    pub fn vfov(&self, aspect_ratio: f64) -> f64 {
+        let half_height = self.sensor_width_mm / (2.0 * aspect_ratio);
+        2.0 * (half_height / self.focal_length_mm).atan().to_degrees()
+    }
+
+    // Diameter of the opening, focal_length / f_number, converted from millimetres to metres
+    pub fn aperture(&self) -> f64 {
+        match self.f_number {
+            Some(f_number) if f_number > 0.0 => self.focal_length_mm / f_number / 1000.0,
+            _ => 0.0,
+        }
+    }
+}
+
+#[derive(Copy, Clone)]
+pub struct Camera {
+    origin: Point3,
+    horizontal: Vec3,
+    vertical: Vec3,
+    lower_left_corner: Vec3,
+    lens_radius: f64,
+    u: Vec3,
+    v: Vec3,
+    w: Vec3,
+    // Size of the viewport one unit in front of the lens, scaled by focus_dist for the
+    // focal plane
+    viewport_width: f64,
+    viewport_height: f64,
+    focus_dist: f64,
+    // focus_dist as passed to new, and the distance to lookat, for with_autofocus
+    manual_focus_dist: f64,
+    lookat_dist: f64,
+    lens_tilt: f64,
+    projection: Projection,
+}
+
+impl Camera {
+    pub fn new(
+        lookfrom: Point3,
+        lookat: Point3,
+        vup: Vec3,
+        vfov: f64,
+        aspect_ratio: f64,
+        aperture: f64,
+        focus_dist: f64,
+    ) -> Camera {
+        let theta = degrees_to_radians(vfov);
+        let h = (theta / 2.0).tan();
+        let viewport_height = 2.0 * h;
+        let viewport_width = aspect_ratio * viewport_height;
+
+        // A degenerate view would turn the whole basis into NaNs and give a black image, so
+        // quietly fall back to something usable. Call check_view first to report it instead.
+        let w = if (lookfrom - lookat).near_zero() {
+            Vec3::new(0.0, 0.0, 1.0)
+        } else {
+            Vec3::unit_vector(lookfrom - lookat)
+        };
+        let up = if is_parallel(vup, w) {
+            least_aligned_axis(w)
+        } else {
+            vup
+        };
+        let u = Vec3::unit_vector(cross(up, w));
+        let v = cross(w, u);
+
+        let origin = lookfrom;
+        let horizontal = focus_dist * viewport_width * u;
+        let vertical = focus_dist * viewport_height * v;
+        let lower_left_corner = origin - (horizontal / 2.0) - (vertical / 2.0) - focus_dist * w;
+
+        let lens_radius = aperture / 2.0;
+        Camera {
+            origin,
+            horizontal,
+            vertical,
+            lower_left_corner,
+            lens_radius,
+            u,
+            v,
+            w,
+            viewport_width,
+            viewport_height,
+            focus_dist,
+            manual_focus_dist: focus_dist,
+            lookat_dist: (lookfrom - lookat).length(),
+            lens_tilt: 0.0,
+            projection: Projection::Perspective,
+        }
+    }
+
+    // Same as new, with the field of view and aperture worked out from a real lens, e.g.
+    // Lens::new(50.0, 36.0).with_f_number(2.8) for a 50mm f/2.8 lens on full frame
+    pub fn from_lens(
+        lookfrom: Point3,
+        lookat: Point3,
+        vup: Vec3,
+        lens: Lens,
+        aspect_ratio: f64,
+        focus_dist: f64,
+    ) -> Camera {
+        Camera::new(
+            lookfrom,
+            lookat,
+            vup,
+            lens.vfov(aspect_ratio),
+            aspect_ratio,
+            lens.aperture(),
+            focus_dist,
+        )
+    }
+
+    // Fails if lookfrom and lookat are the same point, or if vup is zero or points along the
+    // view direction, since neither leaves a way to tell which way the camera faces or is up
+    pub fn check_view(lookfrom: Point3, lookat: Point3, vup: Vec3) -> Result<(), Error> {
+        let view = lookfrom - lookat;
+        if view.near_zero() {
+            return Err(Error::InvalidConfig(String::from(
+                "camera lookfrom and lookat are the same point",
+            )));
+        }
+        if is_parallel(vup, view) {
+            return Err(Error::InvalidConfig(format!(
+                "camera vup ({}, {}, {}) is zero or parallel to the view direction",
+                vup.x, vup.y, vup.z
+            )));
+        }
+        Ok(())
+    }
+
+    // A camera that sees in every direction from lookfrom, with the center of the image facing
+    // lookat. Meant for 2:1 images and has no depth of field.
+    pub fn panorama(lookfrom: Point3, lookat: Point3, vup: Vec3) -> Camera {
+        let mut camera = Camera::new(lookfrom, lookat, vup, 90.0, 2.0, 0.0, 1.0);
+        camera.projection = Projection::Equirectangular;
+        camera
+    }
+
+    // Head-and-shoulders framing for a subject at eye level: a long lens from a few units in
+    // front of it (+z side) with a wide aperture, so the background falls out of focus.
+    pub fn portrait(subject: Point3, aspect_ratio: f64) -> Camera {
+        let lookfrom = subject + Vec3::new(0.0, 0.3, 4.0);
+        let focus_dist = (lookfrom - subject).length();
+        Camera::new(
+            lookfrom,
+            subject,
+            Vec3::new(0.0, 1.0, 0.0),
+            25.0,
+            aspect_ratio,
+            0.25,
+            focus_dist,
+        )
+    }
+
+    // Three-quarter view of the object inside `bounds`, from the front right and a little above.
+    // The camera backs off until the whole bounding sphere of the box fits with some margin, and
+    // is stopped down just enough to soften the far edge.
+    pub fn product(bounds: &Aabb, aspect_ratio: f64) -> Camera {
+        let vfov = 30.0;
+        let center = bounds.centroid();
+        let radius = ((bounds.max() - bounds.min()).length() * 0.5).max(1e-3);
+
+        // The narrower of the vertical and horizontal field of view decides how far back to go
+        let half_vertical = degrees_to_radians(vfov) / 2.0;
+        let half_horizontal = (half_vertical.tan() * aspect_ratio).atan();
+        let half_fov = half_vertical.min(half_horizontal);
+        let distance = 1.15 * radius / half_fov.sin();
+
+        let lookfrom = center + Vec3::unit_vector(Vec3::new(1.0, 0.6, 1.5)) * distance;
+        Camera::new(
+            lookfrom,
+            center,
+            Vec3::new(0.0, 1.0, 0.0),
+            vfov,
+            aspect_ratio,
+            0.01 * distance,
+            distance,
+        )
+    }
+
+    // Wide shot from eye height at the origin, looking down -z with the horizon a little above
+    // the middle of the frame. Pinhole, so everything from the foreground out is sharp.
+    pub fn landscape(aspect_ratio: f64) -> Camera {
+        let lookfrom = Point3::new(0.0, 1.7, 0.0);
+        let lookat = Point3::new(0.0, 1.2, -10.0);
+        Camera::new(
+            lookfrom,
+            lookat,
+            Vec3::new(0.0, 1.0, 0.0),
+            60.0,
+            aspect_ratio,
+            0.0,
+            10.0,
+        )
+    }
+
+    // Tilts the plane of focus about the camera's horizontal axis, in degrees. Positive tilt
+    // pushes the top of the focal plane further away, giving the tilt-shift "miniature" look.
+    pub fn with_lens_tilt(mut self, tilt: f64) -> Camera {
+        self.lens_tilt = degrees_to_radians(tilt);
+        self
+    }
+
+    // With autofocus on, the camera focuses on lookat whatever focus_dist was passed to new,
+    // so the subject stays sharp however the aperture is set. Turning it off goes back to the
+    // explicit focus_dist.
+    pub fn with_autofocus(self, autofocus: bool) -> Camera {
+        let target = if autofocus && self.lookat_dist > 0.0 {
+            self.lookat_dist
+        } else {
+            self.manual_focus_dist
+        };
+        self.refocused(target)
+    }
+
+    // The viewport lives on the focal plane, so it grows with the focus distance to keep the
+    // field of view the same. It is rebuilt from the unit viewport rather than scaled, which
+    // would divide by zero coming from a zero focus_dist.
+    fn refocused(mut self, focus_dist: f64) -> Camera {
+        self.horizontal = focus_dist * self.viewport_width * self.u;
+        self.vertical = focus_dist * self.viewport_height * self.v;
+        self.lower_left_corner =
+            self.origin - (self.horizontal / 2.0) - (self.vertical / 2.0) - focus_dist * self.w;
+        self.focus_dist = focus_dist;
+        self
+    }
+
+    // Direction `v` in the camera's basis: x along the image's right edge, y up it and z
+    // pointing back out of the screen, so a surface facing the camera has a normal near +z
+    pub fn to_camera_space(&self, v: Vec3) -> Vec3 {
+        Vec3::new(dot(v, self.u), dot(v, self.v), dot(v, self.w))
+    }
+
+    pub fn get_ray(self, s: f64, t: f64) -> Ray {
+        if self.projection == Projection::Equirectangular {
+            return Ray::new(self.origin, self.panorama_direction(s, t));
+        }
+
+        self.ray_through_lens(s, t, Vec3::random_in_unit_disk())
+    }
+
+    // Same as get_ray, with the point on the lens chosen by (u1, u2) in [0, 1)^2 through the
+    // concentric disk mapping instead of at random. Feeding it stratified or low-discrepancy
+    // pairs spreads the lens samples evenly, which cleans up out-of-focus areas.
+    pub fn get_ray_with_lens_sample(self, s: f64, t: f64, u1: f64, u2: f64) -> Ray {
+        if self.projection == Projection::Equirectangular {
+            return Ray::new(self.origin, self.panorama_direction(s, t));
+        }
+
+        self.ray_through_lens(s, t, Vec3::concentric_disk_sample(u1, u2))
+    }
+
+    // `disk` is a point on the unit disk, scaled out to the lens radius
+    fn ray_through_lens(self, s: f64, t: f64, disk: Vec3) -> Ray {
+        let rd = self.lens_radius * disk;
+        let offset = self.u * rd.x + self.v * rd.y;
+        let target = self.focus_target(s, t);
+        Ray::new(self.origin + offset, target - self.origin - offset)
+    }
+
+    // Longitude runs from -pi at the left edge to pi at the right, so both edges meet behind the
+    // camera. At the poles cos(latitude) is zero and every longitude collapses onto +/-v.
+    fn panorama_direction(self, s: f64, t: f64) -> Vec3 {
+        let longitude = (s - 0.5) * 2.0 * std::f64::consts::PI;
+        let latitude = (t - 0.5) * std::f64::consts::PI;
+
+        let around = self.u * longitude.sin() - self.w * longitude.cos();
+        around * latitude.cos() + self.v * latitude.sin()
+    }
+
+    // Point that every lens sample for (s, t) converges on. Without tilt this is the flat focal
+    // plane at focus_dist, otherwise the chief ray through the lens center is intersected with
+    // the tilted plane.
+    fn focus_target(self, s: f64, t: f64) -> Point3 {
+        let flat_target = self.lower_left_corner + s * self.horizontal + t * self.vertical;
+        if self.lens_tilt == 0.0 {
+            return flat_target;
+        }
+
+        let plane_normal = self.w * self.lens_tilt.cos() + self.v * self.lens_tilt.sin();
+        let plane_point = self.origin - self.focus_dist * self.w;
+        let chief_ray = flat_target - self.origin;
+
+        let denom = dot(chief_ray, plane_normal);
+        if denom.abs() < 1e-8 {
+            return flat_target;
+        }
+        let distance = dot(plane_point - self.origin, plane_normal) / denom;
+        if distance <= 0.0 {
+            return flat_target;
+        }
+        self.origin + chief_ray * distance
+    }
+}
+
+// True if `a` is zero or within about a ten-thousandth of a degree of +-`b`
+fn is_parallel(a: Vec3, b: Vec3) -> bool {
+    if a.near_zero() || b.near_zero() {
+        return true;
+    }
+    cross(Vec3::unit_vector(a), Vec3::unit_vector(b)).length() < 1e-6
+}
+
+// The world axis furthest from `w`, as a stand-in up vector
+fn least_aligned_axis(w: Vec3) -> Vec3 {
+    let (x, y, z) = (w.x.abs(), w.y.abs(), w.z.abs());
+    if y <= x && y <= z {
+        Vec3::new(0.0, 1.0, 0.0)
+    } else if z <= x {
+        Vec3::new(0.0, 0.0, 1.0)
+    } else {
+        Vec3::new(1.0, 0.0, 0.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn finite(v: Vec3) -> bool {
+        v.x.is_finite() && v.y.is_finite() && v.z.is_finite()
+    }
+
+    #[test]
+    fn vup_along_the_view_still_gives_a_usable_camera() {
+        // Looking straight down with vup pointing straight up
+        let lookfrom = Point3::new(0.0, 5.0, 0.0);
+        let lookat = Point3::new(0.0, 0.0, 0.0);
+        let vup = Vec3::new(0.0, 1.0, 0.0);
+        assert!(Camera::check_view(lookfrom, lookat, vup).is_err());
+
+        let cam = Camera::new(lookfrom, lookat, vup, 40.0, 16.0 / 9.0, 0.0, 5.0);
+        let center = cam.get_ray(0.5, 0.5);
+        let down = Vec3::unit_vector(center.direction);
+        assert!((down.y + 1.0).abs() < 1e-9);
+        for (s, t) in [(0.0, 0.0), (1.0, 0.0), (0.0, 1.0), (1.0, 1.0)] {
+            let r = cam.get_ray(s, t);
+            assert!(finite(r.origin) && finite(r.direction));
+            assert!(r.direction.y < 0.0);
+        }
+    }
+
+    #[test]
+    fn autofocus_recovers_from_a_zero_focus_distance() {
+        let lookfrom = Point3::new(0.0, 0.0, 3.0);
+        let lookat = Point3::new(0.0, 0.0, 0.0);
+        let vup = Vec3::new(0.0, 1.0, 0.0);
+        let cam = Camera::new(lookfrom, lookat, vup, 40.0, 1.0, 0.0, 0.0).with_autofocus(true);
+        let focused = Camera::new(lookfrom, lookat, vup, 40.0, 1.0, 0.0, 3.0);
+        for (s, t) in [(0.0, 0.0), (0.5, 0.5), (1.0, 1.0)] {
+            let (a, b) = (cam.get_ray(s, t).direction, focused.get_ray(s, t).direction);
+            assert!(finite(a));
+            assert!((a - b).length() < 1e-9);
+        }
+    }
+}
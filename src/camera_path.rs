@@ -0,0 +1,259 @@
+use crate::camera::Camera;
+use crate::error::Error;
+use crate::vec3::{Point3, Vec3};
+use std::fs;
+use std::io;
+
+// Camera keyframes for fly-throughs, loaded from a CSV file with one keyframe per line:
+//
+//   time, x, y, z, lookat_x, lookat_y, lookat_z, vfov
+//
+// Blank lines and lines starting with '#' are skipped, and the first line may be a header
+// naming the columns. Times are in seconds and have to increase from line to line. The
+// camera's position and target are interpolated separately, which sidesteps the gimbal
+// problems of interpolating angles.
+
+#[derive(Copy, Clone)]
+pub struct Keyframe {
+    pub time: f64,
+    pub position: Point3,
+    pub lookat: Point3,
+    pub vfov: f64,
+}
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum Interpolation {
+    // Straight lines between keyframes, with a visible kink at each one
+    Linear,
+    // Smooth curve through every keyframe, the default
+    CatmullRom,
+}
+
+pub struct CameraPath {
+    keyframes: Vec<Keyframe>,
+    interpolation: Interpolation,
+}
+
+impl CameraPath {
+    // Needs at least one keyframe, with strictly increasing times
+    pub fn new(keyframes: Vec<Keyframe>) -> Result<CameraPath, Error> {
+        if keyframes.is_empty() {
+            return Err(Error::InvalidConfig(String::from(
+                "a camera path needs at least one keyframe",
+            )));
+        }
+        for pair in keyframes.windows(2) {
+            // Written this way round so NaN times fail as well
+            let increasing = pair[1].time > pair[0].time;
+            if !increasing {
+                return Err(Error::InvalidConfig(format!(
+                    "camera keyframe times must increase, got {} after {}",
+                    pair[1].time, pair[0].time
+                )));
+            }
+        }
+
+        Ok(CameraPath {
+            keyframes,
+            interpolation: Interpolation::CatmullRom,
+        })
+    }
+
+    pub fn load(path: &str) -> Result<CameraPath, Error> {
+        let text = fs::read_to_string(path).map_err(|err| {
+            Error::Io(io::Error::new(
+                err.kind(),
+                format!("Couldn't read '{}': {}", path, err),
+            ))
+        })?;
+
+        CameraPath::parse(&text).map_err(|err| match err {
+            Error::SceneLoad(message) | Error::InvalidConfig(message) => {
+                Error::SceneLoad(format!("Couldn't load '{}': {}", path, message))
+            }
+            other => other,
+        })
+    }
+
+    // Same as load, for CSV text that is already in memory
+    pub fn parse(text: &str) -> Result<CameraPath, Error> {
+        let mut keyframes = Vec::new();
+        let mut first_line = true;
+
+        for (index, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let is_first_line = first_line;
+            first_line = false;
+
+            let fields: Vec<&str> = line.split(',').map(|field| field.trim()).collect();
+            let numbers: Result<Vec<f64>, _> = fields.iter().map(|field| field.parse()).collect();
+            let numbers = match numbers {
+                Ok(numbers) => numbers,
+                // The first line may be a header instead
+                Err(_) if is_first_line && fields[0].parse::<f64>().is_err() => continue,
+                Err(_) => {
+                    return Err(Error::SceneLoad(format!(
+                        "line {}: expected numbers, got '{}'",
+                        index + 1,
+                        line
+                    )))
+                }
+            };
+            if numbers.len() != 8 {
+                return Err(Error::SceneLoad(format!(
+                    "line {}: expected 8 values (time, position, lookat, vfov), got {}",
+                    index + 1,
+                    numbers.len()
+                )));
+            }
+
+            keyframes.push(Keyframe {
+                time: numbers[0],
+                position: Point3::new(numbers[1], numbers[2], numbers[3]),
+                lookat: Point3::new(numbers[4], numbers[5], numbers[6]),
+                vfov: numbers[7],
+            });
+        }
+
+        CameraPath::new(keyframes)
+    }
+
+    pub fn with_interpolation(mut self, interpolation: Interpolation) -> CameraPath {
+        self.interpolation = interpolation;
+        self
+    }
+
+    pub fn keyframes(&self) -> &[Keyframe] {
+        &self.keyframes
+    }
+
+    // Time of the first and last keyframe
+    pub fn start_time(&self) -> f64 {
+        self.keyframes[0].time
+    }
+
+    pub fn end_time(&self) -> f64 {
+        self.keyframes[self.keyframes.len() - 1].time
+    }
+
+    // Interpolated keyframe at `time`, held at the first or last one outside the path. A NaN
+    // time gets the first keyframe.
+    pub fn sample(&self, time: f64) -> Keyframe {
+        let keys = &self.keyframes;
+        let last = keys.len() - 1;
+        // NaN fails every comparison and would leave partition_point below with nothing to find
+        if time.is_nan() {
+            return keys[0];
+        }
+        if time <= keys[0].time || last == 0 {
+            return Keyframe { time, ..keys[0] };
+        }
+        if time >= keys[last].time {
+            return Keyframe { time, ..keys[last] };
+        }
+
+        // keys[i] <= time < keys[i + 1]
+        let i = keys.partition_point(|key| key.time <= time) - 1;
+        let t = (time - keys[i].time) / (keys[i + 1].time - keys[i].time);
+
+        match self.interpolation {
+            Interpolation::Linear => {
+                let (a, b) = (keys[i], keys[i + 1]);
+                Keyframe {
+                    time,
+                    position: a.position + (b.position - a.position) * t,
+                    lookat: a.lookat + (b.lookat - a.lookat) * t,
+                    vfov: a.vfov + (b.vfov - a.vfov) * t,
+                }
+            }
+            Interpolation::CatmullRom => {
+                // The end keyframes are repeated so the curve still passes through them
+                let p0 = keys[i.saturating_sub(1)];
+                let p1 = keys[i];
+                let p2 = keys[i + 1];
+                let p3 = keys[(i + 2).min(last)];
+                Keyframe {
+                    time,
+                    position: catmull_rom(p0.position, p1.position, p2.position, p3.position, t),
+                    lookat: catmull_rom(p0.lookat, p1.lookat, p2.lookat, p3.lookat, t),
+                    vfov: catmull_rom_scalar(p0.vfov, p1.vfov, p2.vfov, p3.vfov, t),
+                }
+            }
+        }
+    }
+
+    // Pinhole camera for `time`, focused on its lookat point
+    pub fn camera(&self, time: f64, vup: Vec3, aspect_ratio: f64) -> Camera {
+        let key = self.sample(time);
+        let focus_dist = (key.position - key.lookat).length().max(1e-3);
+        Camera::new(
+            key.position,
+            key.lookat,
+            vup,
+            key.vfov,
+            aspect_ratio,
+            0.0,
+            focus_dist,
+        )
+    }
+
+    // Time of frame `frame` out of `frame_count`, spread evenly from the first keyframe to the
+    // last so both ends are included
+    pub fn frame_time(&self, frame: usize, frame_count: usize) -> f64 {
+        if frame_count <= 1 {
+            return self.start_time();
+        }
+        let t = frame as f64 / (frame_count - 1) as f64;
+        self.start_time() + (self.end_time() - self.start_time()) * t
+    }
+}
+
+// Uniform Catmull-Rom spline between p1 and p2
+fn catmull_rom(p0: Vec3, p1: Vec3, p2: Vec3, p3: Vec3, t: f64) -> Vec3 {
+    let t2 = t * t;
+    let t3 = t2 * t;
+    (p1 * 2.0
+        + (p2 - p0) * t
+        + (p0 * 2.0 - p1 * 5.0 + p2 * 4.0 - p3) * t2
+        + (p1 * 3.0 - p0 - p2 * 3.0 + p3) * t3)
+        * 0.5
+}
+
+fn catmull_rom_scalar(p0: f64, p1: f64, p2: f64, p3: f64, t: f64) -> f64 {
+    let v = |x: f64| Vec3::new(x, 0.0, 0.0);
+    catmull_rom(v(p0), v(p1), v(p2), v(p3), t).x
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(time: f64, x: f64) -> Keyframe {
+        Keyframe {
+            time,
+            position: Point3::new(x, 0.0, 0.0),
+            lookat: Point3::new(x, 0.0, -1.0),
+            vfov: 40.0,
+        }
+    }
+
+    #[test]
+    fn nan_time_gets_the_first_keyframe() {
+        for interpolation in [Interpolation::Linear, Interpolation::CatmullRom] {
+            let keys = vec![key(0.0, 0.0), key(1.0, 1.0), key(2.0, 4.0)];
+            let path = CameraPath::new(keys)
+                .unwrap()
+                .with_interpolation(interpolation);
+            let sampled = path.sample(f64::NAN);
+            assert_eq!(sampled.time, 0.0);
+            assert_eq!(sampled.position.x, 0.0);
+
+            assert_eq!(path.sample(-1.0).position.x, 0.0);
+            assert_eq!(path.sample(9.0).position.x, 4.0);
+        }
+    }
+}
@@ -0,0 +1,93 @@
+use crate::camera::Camera;
+use crate::hittable::Hittable;
+use crate::render::{luminance, render_pixel, seed_row, RenderSettings};
+use crate::vec3::Color;
+
+// Cross-pixel coverage pass. Thin objects can slip between the samples of a pixel and flicker
+// from frame to frame, but usually some neighbour still catches them. Wherever two neighbouring
+// pixels disagree strongly, both of them and their direct neighbours get extra samples.
+
+#[derive(Copy, Clone)]
+pub struct CoverageSettings {
+    // Difference in average luminance between two neighbours that counts as a disagreement
+    pub threshold: f64,
+    // Samples added to every pixel around a disagreement
+    pub extra_samples: i64,
+}
+
+impl Default for CoverageSettings {
+    fn default() -> CoverageSettings {
+        CoverageSettings {
+            threshold: 0.25,
+            extra_samples: 32,
+        }
+    }
+}
+
+// Refines `pixels`, laid out like render's output, in place
+pub fn refine_coverage(
+    world: &dyn Hittable,
+    cam: &Camera,
+    settings: &RenderSettings,
+    pixels: &mut [Color],
+    coverage: &CoverageSettings,
+) {
+    let image_height = settings.image_height();
+    let region = settings.region();
+    let width = region.width();
+    let height = region.height();
+    let samples_per_pixel = settings.samples_per_pixel as f64;
+    let index = |x: i64, y: i64| (y * width + x) as usize;
+    let average = |pixel: Color| luminance(pixel) / samples_per_pixel;
+
+    let mut marked = vec![false; pixels.len()];
+    let mut mark_around = |x: i64, y: i64| {
+        for (nx, ny) in [(x, y), (x - 1, y), (x + 1, y), (x, y - 1), (x, y + 1)] {
+            if nx >= 0 && ny >= 0 && nx < width && ny < height {
+                marked[index(nx, ny)] = true;
+            }
+        }
+    };
+
+    // Right and lower neighbours are enough to visit every adjacent pair once
+    for y in 0..height {
+        for x in 0..width {
+            let here = average(pixels[index(x, y)]);
+            for (nx, ny) in [(x + 1, y), (x, y + 1)] {
+                if nx < width && ny < height {
+                    let there = average(pixels[index(nx, ny)]);
+                    if (here - there).abs() > coverage.threshold {
+                        mark_around(x, y);
+                        mark_around(nx, ny);
+                    }
+                }
+            }
+        }
+    }
+
+    let mut extra_settings = settings.clone();
+    extra_settings.samples_per_pixel = coverage.extra_samples;
+    let extra = coverage.extra_samples as f64;
+
+    for y in 0..height {
+        // Runs on the calling thread, whichever threads rendered the rows
+        seed_row(settings, 1, region.y0 + y);
+        for x in 0..width {
+            if !marked[index(x, y)] {
+                continue;
+            }
+
+            // Buffers only cover the crop, render_pixel takes full-frame coordinates counting
+            // up from the bottom
+            let draw_width = region.x0 + x;
+            let draw_height = image_height - 1 - (region.y0 + y);
+            let extra_sum =
+                render_pixel(world, cam, &extra_settings, draw_width, draw_height, None);
+
+            // Both sums are scaled to their own sample count, weight them by it and rescale the
+            // result back to samples_per_pixel
+            let pixel = &mut pixels[index(x, y)];
+            *pixel = (*pixel + extra_sum) * (samples_per_pixel / (samples_per_pixel + extra));
+        }
+    }
+}
@@ -0,0 +1,84 @@
+use crate::render::{Aovs, RenderSettings};
+use crate::vec3::{Color, Vec3};
+
+// Settings for the joint bilateral denoiser. Each sigma controls how quickly a neighbor's weight
+// falls off as it differs from the center pixel, in screen distance or in one of the buffers.
+
+#[derive(Copy, Clone)]
+pub struct DenoiseSettings {
+    // Half-width of the filter window, in pixels
+    pub radius: i64,
+    pub sigma_spatial: f64,
+    pub sigma_color: f64,
+    pub sigma_albedo: f64,
+    pub sigma_normal: f64,
+}
+
+impl Default for DenoiseSettings {
+    fn default() -> DenoiseSettings {
+        DenoiseSettings {
+            radius: 3,
+            sigma_spatial: 2.0,
+            sigma_color: 0.4,
+            sigma_albedo: 0.1,
+            sigma_normal: 0.2,
+        }
+    }
+}
+
+fn falloff(distance_squared: f64, sigma: f64) -> f64 {
+    f64::exp(-distance_squared / (2.0 * sigma * sigma))
+}
+
+// Smooths the beauty pass while keeping edges, by only averaging with neighbors whose color,
+// albedo and normal are similar to the center pixel. `pixels` holds per-pixel sample sums as
+// returned by render_with_aovs, and the result is in the same form.
+pub fn denoise(
+    pixels: &[Color],
+    aovs: &Aovs,
+    settings: &RenderSettings,
+    denoise: &DenoiseSettings,
+) -> Vec<Color> {
+    // Buffers only cover the crop when there is one
+    let image_width = settings.region().width();
+    let image_height = settings.region().height();
+    let scale = 1.0 / settings.samples_per_pixel as f64;
+    let index = |x: i64, y: i64| (y * image_width + x) as usize;
+
+    let mut output = Vec::with_capacity(pixels.len());
+
+    for y in 0..image_height {
+        for x in 0..image_width {
+            let center = index(x, y);
+            let center_color = pixels[center] * scale;
+            let center_albedo = aovs.albedo[center];
+            let center_normal = aovs.normal[center];
+
+            let mut sum = Color::default();
+            let mut weight_sum = 0.0;
+
+            for ny in (y - denoise.radius).max(0)..=(y + denoise.radius).min(image_height - 1) {
+                for nx in (x - denoise.radius).max(0)..=(x + denoise.radius).min(image_width - 1) {
+                    let neighbor = index(nx, ny);
+                    let offset = ((nx - x) * (nx - x) + (ny - y) * (ny - y)) as f64;
+                    let color_diff: Vec3 = pixels[neighbor] * scale - center_color;
+                    let albedo_diff = aovs.albedo[neighbor] - center_albedo;
+                    let normal_diff = aovs.normal[neighbor] - center_normal;
+
+                    let weight = falloff(offset, denoise.sigma_spatial)
+                        * falloff(color_diff.length_squared(), denoise.sigma_color)
+                        * falloff(albedo_diff.length_squared(), denoise.sigma_albedo)
+                        * falloff(normal_diff.length_squared(), denoise.sigma_normal);
+
+                    sum += pixels[neighbor] * weight;
+                    weight_sum += weight;
+                }
+            }
+
+            // The center pixel always has weight 1, so weight_sum can't be zero
+            output.push(sum / weight_sum);
+        }
+    }
+
+    output
+}
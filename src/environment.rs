@@ -0,0 +1,319 @@
+use crate::error::Error;
+use crate::vec3::{Color, Vec3};
+use std::fs;
+use std::io;
+use std::path::Path;
+
+// Equirectangular environment map holding linear radiance, looked up by direction for rays that
+// leave the scene. The image's center column faces -z and its top row faces +y.
+
+pub struct EnvironmentMap {
+    width: usize,
+    height: usize,
+    pixels: Vec<Color>,
+    // Loaded from an 8-bit image, so nothing in it is brighter than 1.0
+    clipped: bool,
+}
+
+impl EnvironmentMap {
+    pub fn new(width: usize, height: usize, pixels: Vec<Color>) -> Result<EnvironmentMap, Error> {
+        // value() needs at least one pixel, and a short raster would index past the end
+        if width == 0 || height == 0 || width.checked_mul(height) != Some(pixels.len()) {
+            return Err(Error::SceneLoad(String::from(
+                "image is empty or truncated",
+            )));
+        }
+        Ok(EnvironmentMap {
+            width,
+            height,
+            pixels,
+            clipped: false,
+        })
+    }
+
+    // Loads a Radiance .hdr file at full float range. Plain 8-bit .ppm images also work but can't
+    // hold anything brighter than 1.0, is_clipped tells the caller to warn about that.
+    pub fn load(path: &str) -> Result<EnvironmentMap, Error> {
+        let extension = Path::new(path)
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .map(|ext| ext.to_ascii_lowercase());
+        let bytes = fs::read(path).map_err(|err| {
+            Error::Io(io::Error::new(
+                err.kind(),
+                format!("Couldn't read '{}': {}", path, err),
+            ))
+        })?;
+
+        match extension.as_deref() {
+            Some("hdr") => parse_hdr(&bytes),
+            Some("ppm") => parse_ppm(&bytes),
+            Some("exr") => Err(String::from(
+                "OpenEXR environment maps aren't supported in this build, convert to .hdr",
+            )),
+            _ => Err(String::from("Environment maps must be .hdr or .ppm files")),
+        }
+        .map_err(Error::SceneLoad)
+        .and_then(|(width, height, pixels)| EnvironmentMap::new(width, height, pixels))
+        .map_err(|err| match err {
+            Error::SceneLoad(message) => {
+                Error::SceneLoad(format!("Couldn't load '{}': {}", path, message))
+            }
+            other => other,
+        })
+        .map(|map| EnvironmentMap {
+            clipped: extension.as_deref() == Some("ppm"),
+            ..map
+        })
+    }
+
+    pub fn is_clipped(&self) -> bool {
+        self.clipped
+    }
+
+    pub fn value(&self, direction: Vec3) -> Color {
+        let unit_direction = Vec3::unit_vector(direction);
+        let longitude = unit_direction.x.atan2(-unit_direction.z);
+        let latitude = unit_direction.y.clamp(-1.0, 1.0).asin();
+
+        let s = longitude / (2.0 * std::f64::consts::PI) + 0.5;
+        let t = 0.5 - latitude / std::f64::consts::PI;
+
+        let x = ((s * self.width as f64) as usize).min(self.width - 1);
+        let y = ((t * self.height as f64) as usize).min(self.height - 1);
+        self.pixels[y * self.width + x]
+    }
+}
+
+// Radiance RGBE: a text header, a resolution line, then scanlines that are either flat RGBE
+// quads or the newer per-channel run-length encoding
+fn parse_hdr(bytes: &[u8]) -> Result<(usize, usize, Vec<Color>), String> {
+    let mut pos = 0;
+    let next_line = |pos: &mut usize| -> Option<String> {
+        let start = *pos;
+        let end = start + bytes[start..].iter().position(|&b| b == b'\n')?;
+        *pos = end + 1;
+        Some(
+            String::from_utf8_lossy(&bytes[start..end])
+                .trim()
+                .to_string(),
+        )
+    };
+
+    let magic = next_line(&mut pos).ok_or("empty file")?;
+    if !magic.starts_with("#?") {
+        return Err(String::from("not a Radiance file"));
+    }
+    loop {
+        let line = next_line(&mut pos).ok_or("header never ends")?;
+        if line.is_empty() {
+            break;
+        }
+        if let Some(format) = line.strip_prefix("FORMAT=") {
+            if format != "32-bit_rle_rgbe" {
+                return Err(format!("unsupported pixel format {}", format));
+            }
+        }
+    }
+
+    let resolution = next_line(&mut pos).ok_or("missing resolution")?;
+    let fields: Vec<&str> = resolution.split_whitespace().collect();
+    if fields.len() != 4 || fields[0] != "-Y" || fields[2] != "+X" {
+        return Err(format!("unsupported resolution line '{}'", resolution));
+    }
+    let height: usize = fields[1].parse().map_err(|_| "bad height")?;
+    let width: usize = fields[3].parse().map_err(|_| "bad width")?;
+    // Scanlines are read into a buffer of this width, so an empty one can't be read at all
+    if width == 0 || height == 0 {
+        return Err(String::from("image is empty"));
+    }
+
+    let data = &bytes[pos..];
+
+    // Even fully run-length encoded, a pixel takes more than 1/16 of a byte, so anything larger
+    // can't be real and isn't worth allocating for
+    let pixel_count = width.checked_mul(height).ok_or("image too large")?;
+    if pixel_count > data.len().saturating_mul(16) {
+        return Err(String::from("pixel data ends early"));
+    }
+
+    let mut offset = 0;
+    let mut pixels = Vec::with_capacity(pixel_count);
+    let mut scanline = vec![[0u8; 4]; width];
+
+    for _ in 0..height {
+        read_hdr_scanline(data, &mut offset, &mut scanline)?;
+        pixels.extend(scanline.iter().map(|&rgbe| rgbe_to_color(rgbe)));
+    }
+
+    Ok((width, height, pixels))
+}
+
+fn read_hdr_scanline(
+    data: &[u8],
+    offset: &mut usize,
+    scanline: &mut [[u8; 4]],
+) -> Result<(), String> {
+    let width = scanline.len();
+    let mut byte = || -> Result<u8, String> {
+        let value = *data.get(*offset).ok_or("pixel data ends early")?;
+        *offset += 1;
+        Ok(value)
+    };
+
+    let head = [byte()?, byte()?, byte()?, byte()?];
+    let run_length_encoded =
+        (8..0x8000).contains(&width) && head[0] == 2 && head[1] == 2 && head[2] & 0x80 == 0;
+
+    if !run_length_encoded {
+        // Flat quads, where (1, 1, 1, n) repeats the previous pixel n times
+        let mut x = 0;
+        let mut quad = head;
+        loop {
+            if quad[0] == 1 && quad[1] == 1 && quad[2] == 1 && x > 0 {
+                let previous = scanline[x - 1];
+                for _ in 0..quad[3] {
+                    *scanline.get_mut(x).ok_or("run overflows scanline")? = previous;
+                    x += 1;
+                }
+            } else {
+                scanline[x] = quad;
+                x += 1;
+            }
+            if x >= width {
+                return Ok(());
+            }
+            quad = [byte()?, byte()?, byte()?, byte()?];
+        }
+    }
+
+    if ((head[2] as usize) << 8 | head[3] as usize) != width {
+        return Err(String::from("scanline width mismatch"));
+    }
+
+    for channel in 0..4 {
+        let mut x = 0;
+        while x < width {
+            let count = byte()? as usize;
+            if count > 128 {
+                let value = byte()?;
+                for _ in 0..count - 128 {
+                    scanline.get_mut(x).ok_or("run overflows scanline")?[channel] = value;
+                    x += 1;
+                }
+            } else {
+                for _ in 0..count {
+                    let value = byte()?;
+                    scanline.get_mut(x).ok_or("run overflows scanline")?[channel] = value;
+                    x += 1;
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn rgbe_to_color(rgbe: [u8; 4]) -> Color {
+    if rgbe[3] == 0 {
+        return Color::default();
+    }
+    let scale = 2f64.powi(rgbe[3] as i32 - 136);
+    Color::new(
+        (rgbe[0] as f64 + 0.5) * scale,
+        (rgbe[1] as f64 + 0.5) * scale,
+        (rgbe[2] as f64 + 0.5) * scale,
+    )
+}
+
+// Binary (P6) or ASCII (P3) PPM with 8-bit channels. Values are assumed to be gamma 2 encoded,
+// like the renderer's own output, and are squared back to linear.
+fn parse_ppm(bytes: &[u8]) -> Result<(usize, usize, Vec<Color>), String> {
+    let mut pos = 0;
+    let token = |pos: &mut usize| -> Option<String> {
+        loop {
+            while *pos < bytes.len() && bytes[*pos].is_ascii_whitespace() {
+                *pos += 1;
+            }
+            if *pos < bytes.len() && bytes[*pos] == b'#' {
+                while *pos < bytes.len() && bytes[*pos] != b'\n' {
+                    *pos += 1;
+                }
+                continue;
+            }
+            break;
+        }
+        let start = *pos;
+        while *pos < bytes.len() && !bytes[*pos].is_ascii_whitespace() {
+            *pos += 1;
+        }
+        (start < *pos).then(|| String::from_utf8_lossy(&bytes[start..*pos]).to_string())
+    };
+    let number = |pos: &mut usize| -> Result<usize, String> {
+        token(pos)
+            .and_then(|t| t.parse().ok())
+            .ok_or_else(|| String::from("bad PPM header"))
+    };
+
+    let magic = token(&mut pos).ok_or("empty file")?;
+    let width = number(&mut pos)?;
+    let height = number(&mut pos)?;
+    let max_value = number(&mut pos)?;
+    if max_value == 0 || max_value > 255 {
+        return Err(String::from("only 8-bit PPM files are supported"));
+    }
+
+    let value_count = width
+        .checked_mul(height)
+        .and_then(|count| count.checked_mul(3))
+        .ok_or("image too large")?;
+    if value_count > bytes.len() {
+        return Err(String::from("pixel data ends early"));
+    }
+
+    let channels: Vec<u8> = match magic.as_str() {
+        "P6" => {
+            // A single whitespace byte separates the header from the raster
+            let start = pos + 1;
+            bytes
+                .get(start..start + value_count)
+                .ok_or("pixel data ends early")?
+                .to_vec()
+        }
+        "P3" => {
+            let mut values = Vec::with_capacity(value_count);
+            for _ in 0..value_count {
+                values.push(number(&mut pos)?.min(255) as u8);
+            }
+            values
+        }
+        _ => return Err(String::from("not a PPM file")),
+    };
+
+    let to_linear = |value: u8| {
+        let encoded = value as f64 / max_value as f64;
+        encoded * encoded
+    };
+    let pixels = channels
+        .chunks_exact(3)
+        .map(|rgb| Color::new(to_linear(rgb[0]), to_linear(rgb[1]), to_linear(rgb[2])))
+        .collect();
+
+    Ok((width, height, pixels))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn new_rejects_a_raster_of_the_wrong_size() {
+        let gray = Color::new(0.5, 0.5, 0.5);
+        assert!(EnvironmentMap::new(2, 2, vec![gray; 3]).is_err());
+        assert!(EnvironmentMap::new(0, 4, Vec::new()).is_err());
+        assert!(EnvironmentMap::new(usize::MAX, 2, vec![gray; 2]).is_err());
+
+        let map = EnvironmentMap::new(2, 1, vec![gray; 2]).unwrap();
+        assert_eq!(map.value(Vec3::new(0.0, 0.0, -1.0)).x, 0.5);
+    }
+}
@@ -0,0 +1,40 @@
+use std::fmt;
+use std::io;
+
+// Everything the library can fail with. Library code returns these instead of panicking on
+// bad input, and the messages are written to be shown to the user as they are.
+
+#[derive(Debug)]
+pub enum Error {
+    // Reading or writing a file failed
+    Io(io::Error),
+    // The render settings don't describe something that can be rendered
+    InvalidConfig(String),
+    // A scene, or something it depends on like an environment map, couldn't be loaded
+    SceneLoad(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Io(err) => write!(f, "{}", err),
+            Error::InvalidConfig(message) => write!(f, "{}", message),
+            Error::SceneLoad(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Error {
+        Error::Io(err)
+    }
+}
@@ -0,0 +1,483 @@
+use crate::aabb::Aabb;
+use crate::material::{Material, Metal};
+use crate::ray::Ray;
+use crate::vec3::{dot, Color, Point3, Vec3};
+use rand::{Rng, RngCore};
+use std::any::Any;
+use std::ops::Index;
+use std::sync::{Arc, OnceLock};
+
+// Hit record
+#[derive(Clone)]
+pub struct HitRecord {
+    pub p: Point3,
+    pub normal: Vec3,
+    pub mat_ptr: Arc<dyn Material>,
+    pub t: f64,
+    pub front_face: bool,
+    // Set by WithId, 0 for objects that don't have one
+    pub object_id: u32,
+}
+
+impl HitRecord {
+    // Stores the normal facing against the ray and records which side was hit. A ray leaving an
+    // object from the inside, e.g. a camera placed in a glass sphere, gets front_face = false.
+    pub fn set_face_normal(&mut self, r: Ray, outward_normal: Vec3) {
+        if dot(r.direction, outward_normal) < 0.0 {
+            self.front_face = true;
+            self.normal = outward_normal;
+        } else {
+            self.front_face = false;
+            self.normal = -outward_normal;
+        }
+    }
+}
+
+impl Default for HitRecord {
+    fn default() -> HitRecord {
+        HitRecord {
+            p: Vec3::default(),
+            normal: Vec3::default(),
+            mat_ptr: placeholder_material(),
+            t: 0.0,
+            front_face: false,
+            object_id: 0,
+        }
+    }
+}
+
+// Material of a record nothing has been written to yet. hit2 makes a default record on every
+// call, so they all share one instead of allocating their own.
+fn placeholder_material() -> Arc<dyn Material> {
+    static PLACEHOLDER: OnceLock<Arc<dyn Material>> = OnceLock::new();
+    PLACEHOLDER
+        .get_or_init(|| Arc::new(Metal::new(Color::new(0.7, 0.3, 0.3), 1.0)))
+        .clone()
+}
+
+// Hittable trait and its associated functions
+
+// True if [t_min, t_max] contains at least one value. Inverted intervals (which CSG and some
+// camera math can produce) and NaN bounds are rejected, zero-width ones are allowed.
+pub fn valid_interval(t_min: f64, t_max: f64) -> bool {
+    t_min <= t_max
+}
+
+pub trait Hittable: Send + Sync {
+    fn hit(&self, r: Ray, t_min: f64, t_max: f64, rec: &mut HitRecord) -> bool;
+
+    // Same query as `hit`, but returns the closest hit instead of writing into an out-parameter
+    fn hit2(&self, r: Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let mut rec = HitRecord::default();
+        if self.hit(r, t_min, t_max, &mut rec) {
+            Some(rec)
+        } else {
+            None
+        }
+    }
+
+    // True if anything lies along `r` within [t_min, t_max], for shadow rays that only care
+    // whether the way is blocked. Groups override it to stop at the first hit they find rather
+    // than searching for the closest one.
+    fn hit_any(&self, r: Ray, t_min: f64, t_max: f64) -> bool {
+        self.hit2(r, t_min, t_max).is_some()
+    }
+
+    // Intersects a packet of rays, writing each ray's closest hit into the matching slot of
+    // `out`. Primitives can override this to test several rays at once, the default just loops
+    // over `hit` so results are identical to single-ray queries.
+    fn hit_batch(&self, rays: &[Ray], t_min: f64, t_max: f64, out: &mut [Option<HitRecord>]) {
+        debug_assert_eq!(rays.len(), out.len());
+
+        for (r, slot) in rays.iter().zip(out.iter_mut()) {
+            *slot = self.hit2(*r, t_min, t_max);
+        }
+    }
+
+    // Box around everything the object can be hit on, for BvhNode. None for objects without
+    // one, which a BVH has to test on every ray.
+    fn bounding_box(&self) -> Option<Aabb> {
+        None
+    }
+
+    // Density, per unit solid angle, with which `random` picks `direction` when standing at
+    // `origin`. Only shapes that can be sampled as lights implement these two, the rest report
+    // a density of zero.
+    fn pdf_value(&self, _origin: Point3, _direction: Vec3) -> f64 {
+        0.0
+    }
+
+    // Direction from `origin` towards a random point of the object, all randomness coming from
+    // `rng` like in Material::scatter
+    fn random(&self, _origin: Point3, _rng: &mut dyn RngCore) -> Vec3 {
+        Vec3::new(1.0, 0.0, 0.0)
+    }
+
+    // Independent copy of the object, including everything it holds (children, materials),
+    // so the copy can be changed without touching the original. Arc::clone only shares.
+    fn clone_box(&self) -> Arc<dyn Hittable>;
+
+    // Lets tools tell primitives apart and recover them with downcast_ref
+    fn as_any(&self) -> &dyn Any;
+}
+
+// List storing hittable objects
+
+pub struct HittableList {
+    objects: Vec<Arc<dyn Hittable>>,
+}
+
+impl Default for HittableList {
+    fn default() -> HittableList {
+        HittableList::new()
+    }
+}
+
+impl HittableList {
+    pub fn new() -> HittableList {
+        HittableList {
+            objects: Vec::new(),
+        }
+    }
+
+    //fn clear(&mut self) {
+    //    self.objects.clear();
+    //}
+
+    pub fn add(&mut self, object: Arc<dyn Hittable>) {
+        self.objects.push(object);
+    }
+
+    // Read-only access to the objects, e.g. to count primitives or wrap each one in a
+    // WithMaterial. Adding still has to go through `add`.
+    pub fn iter(&self) -> std::slice::Iter<'_, Arc<dyn Hittable>> {
+        self.objects.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.objects.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.objects.is_empty()
+    }
+}
+
+impl Index<usize> for HittableList {
+    type Output = Arc<dyn Hittable>;
+
+    fn index(&self, index: usize) -> &Arc<dyn Hittable> {
+        &self.objects[index]
+    }
+}
+
+impl<'a> IntoIterator for &'a HittableList {
+    type Item = &'a Arc<dyn Hittable>;
+    type IntoIter = std::slice::Iter<'a, Arc<dyn Hittable>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.objects.iter()
+    }
+}
+
+impl Hittable for HittableList {
+    fn hit(&self, r: Ray, t_min: f64, t_max: f64, rec: &mut HitRecord) -> bool {
+        if !valid_interval(t_min, t_max) {
+            return false;
+        }
+
+        let mut hit_anything = false;
+        let mut closest_so_far = t_max;
+
+        for object in &self.objects {
+            if let Some(temp_rec) = object.hit2(r, t_min, closest_so_far) {
+                hit_anything = true;
+                closest_so_far = temp_rec.t;
+                *rec = temp_rec;
+            }
+        }
+
+        hit_anything
+    }
+
+    fn hit_any(&self, r: Ray, t_min: f64, t_max: f64) -> bool {
+        if !valid_interval(t_min, t_max) {
+            return false;
+        }
+        self.objects
+            .iter()
+            .any(|object| object.hit_any(r, t_min, t_max))
+    }
+
+    // None if the list is empty or holds anything unbounded
+    fn bounding_box(&self) -> Option<Aabb> {
+        let mut objects = self.objects.iter();
+        let first = objects.next()?.bounding_box()?;
+        objects.try_fold(first, |bbox, object| {
+            Some(bbox.surrounding(object.bounding_box()?))
+        })
+    }
+
+    // Picks one of the objects uniformly, so the density is the average of theirs
+    fn pdf_value(&self, origin: Point3, direction: Vec3) -> f64 {
+        if self.objects.is_empty() {
+            return 0.0;
+        }
+        let sum: f64 = self
+            .objects
+            .iter()
+            .map(|object| object.pdf_value(origin, direction))
+            .sum();
+        sum / self.objects.len() as f64
+    }
+
+    fn random(&self, origin: Point3, rng: &mut dyn RngCore) -> Vec3 {
+        if self.objects.is_empty() {
+            return Vec3::new(1.0, 0.0, 0.0);
+        }
+        let index = rng.gen_range(0..self.objects.len());
+        self.objects[index].random(origin, rng)
+    }
+
+    fn clone_box(&self) -> Arc<dyn Hittable> {
+        Arc::new(HittableList {
+            objects: self
+                .objects
+                .iter()
+                .map(|object| object.clone_box())
+                .collect(),
+        })
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+// Wraps another hittable and swaps in a different material, so the same geometry can be reused
+// with several materials without rebuilding it
+
+pub struct WithMaterial {
+    object: Arc<dyn Hittable>,
+    mat_ptr: Arc<dyn Material>,
+}
+
+impl WithMaterial {
+    pub fn new(object: Arc<dyn Hittable>, material: Arc<dyn Material>) -> WithMaterial {
+        WithMaterial {
+            object,
+            mat_ptr: material,
+        }
+    }
+
+    pub fn material(&self) -> &Arc<dyn Material> {
+        &self.mat_ptr
+    }
+}
+
+impl Hittable for WithMaterial {
+    fn hit(&self, r: Ray, t_min: f64, t_max: f64, rec: &mut HitRecord) -> bool {
+        match self.object.hit2(r, t_min, t_max) {
+            Some(child_rec) => {
+                *rec = child_rec;
+                rec.mat_ptr = self.mat_ptr.clone();
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn hit_any(&self, r: Ray, t_min: f64, t_max: f64) -> bool {
+        self.object.hit_any(r, t_min, t_max)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        self.object.bounding_box()
+    }
+
+    fn pdf_value(&self, origin: Point3, direction: Vec3) -> f64 {
+        self.object.pdf_value(origin, direction)
+    }
+
+    fn random(&self, origin: Point3, rng: &mut dyn RngCore) -> Vec3 {
+        self.object.random(origin, rng)
+    }
+
+    fn clone_box(&self) -> Arc<dyn Hittable> {
+        Arc::new(WithMaterial::new(
+            self.object.clone_box(),
+            self.mat_ptr.clone_box(),
+        ))
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+// Swaps which side of the wrapped object counts as the front, e.g. to turn a one-sided light
+// around. The shading normal still faces the incoming ray.
+
+pub struct FlipFace {
+    object: Arc<dyn Hittable>,
+}
+
+impl FlipFace {
+    pub fn new(object: Arc<dyn Hittable>) -> FlipFace {
+        FlipFace { object }
+    }
+}
+
+impl Hittable for FlipFace {
+    fn hit(&self, r: Ray, t_min: f64, t_max: f64, rec: &mut HitRecord) -> bool {
+        match self.object.hit2(r, t_min, t_max) {
+            Some(child_rec) => {
+                *rec = child_rec;
+                rec.front_face = !rec.front_face;
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn hit_any(&self, r: Ray, t_min: f64, t_max: f64) -> bool {
+        self.object.hit_any(r, t_min, t_max)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        self.object.bounding_box()
+    }
+
+    fn pdf_value(&self, origin: Point3, direction: Vec3) -> f64 {
+        self.object.pdf_value(origin, direction)
+    }
+
+    fn random(&self, origin: Point3, rng: &mut dyn RngCore) -> Vec3 {
+        self.object.random(origin, rng)
+    }
+
+    fn clone_box(&self) -> Arc<dyn Hittable> {
+        Arc::new(FlipFace::new(self.object.clone_box()))
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+// Tags every hit on the wrapped object with `id`, which ends up in the object ID AOV. IDs
+// should be non-zero since 0 is what untagged objects and the background get.
+
+pub struct WithId {
+    object: Arc<dyn Hittable>,
+    id: u32,
+}
+
+impl WithId {
+    pub fn new(object: Arc<dyn Hittable>, id: u32) -> WithId {
+        WithId { object, id }
+    }
+
+    pub fn id(&self) -> u32 {
+        self.id
+    }
+}
+
+impl Hittable for WithId {
+    fn hit(&self, r: Ray, t_min: f64, t_max: f64, rec: &mut HitRecord) -> bool {
+        match self.object.hit2(r, t_min, t_max) {
+            Some(child_rec) => {
+                *rec = child_rec;
+                rec.object_id = self.id;
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn hit_any(&self, r: Ray, t_min: f64, t_max: f64) -> bool {
+        self.object.hit_any(r, t_min, t_max)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        self.object.bounding_box()
+    }
+
+    fn pdf_value(&self, origin: Point3, direction: Vec3) -> f64 {
+        self.object.pdf_value(origin, direction)
+    }
+
+    fn random(&self, origin: Point3, rng: &mut dyn RngCore) -> Vec3 {
+        self.object.random(origin, rng)
+    }
+
+    fn clone_box(&self) -> Arc<dyn Hittable> {
+        Arc::new(WithId::new(self.object.clone_box(), self.id))
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::Lambertian;
+    use crate::sphere::Sphere;
+
+    fn sphere_at(z: f64) -> Arc<dyn Hittable> {
+        let material = Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+        Arc::new(Sphere::new(Point3::new(0.0, 0.0, z), 0.5, material))
+    }
+
+    // Looks down -z from the origin, so a sphere at z = -d is first hit at t = d - 0.5
+    fn forward_ray() -> Ray {
+        Ray::new(Point3::default(), Vec3::new(0.0, 0.0, -1.0))
+    }
+
+    #[test]
+    fn inverted_interval_never_hits() {
+        let sphere = sphere_at(-2.0);
+        assert!(sphere.hit2(forward_ray(), 1.0, 10.0).is_some());
+
+        assert!(sphere.hit2(forward_ray(), 10.0, 1.0).is_none());
+        assert!(!sphere.hit_any(forward_ray(), 10.0, 1.0));
+
+        let mut list = HittableList::new();
+        list.add(sphere_at(-2.0));
+        list.add(sphere_at(-4.0));
+        assert!(list.hit2(forward_ray(), 10.0, 1.0).is_none());
+        assert!(!list.hit_any(forward_ray(), 10.0, 1.0));
+    }
+
+    #[test]
+    fn nan_bounds_never_hit() {
+        let sphere = sphere_at(-2.0);
+        assert!(sphere.hit2(forward_ray(), f64::NAN, 10.0).is_none());
+        assert!(sphere.hit2(forward_ray(), 0.0, f64::NAN).is_none());
+    }
+
+    #[test]
+    fn list_respects_small_t_max() {
+        let mut list = HittableList::new();
+        list.add(sphere_at(-4.0));
+        list.add(sphere_at(-2.0));
+
+        // Both spheres lie beyond t_max
+        assert!(list.hit2(forward_ray(), 1e-5, 0.5).is_none());
+        assert!(!list.hit_any(forward_ray(), 1e-5, 0.5));
+
+        // Only the near one is in range, and added last it still has to be the one returned
+        let rec = list
+            .hit2(forward_ray(), 1e-5, 2.0)
+            .expect("near sphere is in range");
+        assert!((rec.t - 1.5).abs() < 1e-9);
+
+        // The far sphere's front at t = 3.5 is the closest surface after the near one's back
+        let rec = list
+            .hit2(forward_ray(), 2.6, 10.0)
+            .expect("far sphere is in range");
+        assert!((rec.t - 3.5).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,233 @@
+use crate::aabb::Aabb;
+use crate::hittable::{HitRecord, Hittable};
+use crate::ray::Ray;
+use crate::utility::degrees_to_radians;
+use crate::vec3::{Point3, Vec3};
+use std::any::Any;
+use std::sync::Arc;
+
+// Affine transform, a linear part followed by a translation. The inverse of the linear part is
+// kept alongside it so rays can be taken into object space without inverting a matrix per hit.
+
+#[derive(Copy, Clone)]
+pub struct Transform {
+    linear: [[f64; 3]; 3],
+    inverse: [[f64; 3]; 3],
+    translation: Vec3,
+}
+
+const IDENTITY: [[f64; 3]; 3] = [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]];
+
+impl Default for Transform {
+    fn default() -> Transform {
+        Transform::identity()
+    }
+}
+
+impl Transform {
+    pub fn identity() -> Transform {
+        Transform {
+            linear: IDENTITY,
+            inverse: IDENTITY,
+            translation: Vec3::default(),
+        }
+    }
+
+    pub fn translation(offset: Vec3) -> Transform {
+        Transform {
+            translation: offset,
+            ..Transform::identity()
+        }
+    }
+
+    // Scales along each axis, none of the factors may be zero
+    pub fn scaling(factors: Vec3) -> Transform {
+        Transform {
+            linear: diagonal(factors),
+            inverse: diagonal(Vec3::new(1.0 / factors.x, 1.0 / factors.y, 1.0 / factors.z)),
+            translation: Vec3::default(),
+        }
+    }
+
+    // Rotates counter-clockwise by `degrees` about `axis` (a right-handed rotation, looking
+    // down the axis towards the origin)
+    pub fn rotation(axis: Vec3, degrees: f64) -> Transform {
+        let a = Vec3::unit_vector(axis);
+        let theta = degrees_to_radians(degrees);
+        let (sin, cos) = theta.sin_cos();
+        let k = 1.0 - cos;
+
+        let linear = [
+            [
+                cos + a.x * a.x * k,
+                a.x * a.y * k - a.z * sin,
+                a.x * a.z * k + a.y * sin,
+            ],
+            [
+                a.y * a.x * k + a.z * sin,
+                cos + a.y * a.y * k,
+                a.y * a.z * k - a.x * sin,
+            ],
+            [
+                a.z * a.x * k - a.y * sin,
+                a.z * a.y * k + a.x * sin,
+                cos + a.z * a.z * k,
+            ],
+        ];
+
+        // Rotations are orthogonal, so the inverse is just the transpose
+        Transform {
+            linear,
+            inverse: transpose(linear),
+            translation: Vec3::default(),
+        }
+    }
+
+    // Applies self first and then `next`, e.g. scale, then rotate, then move into place
+    pub fn then(self, next: Transform) -> Transform {
+        Transform {
+            linear: multiply(next.linear, self.linear),
+            inverse: multiply(self.inverse, next.inverse),
+            translation: next.apply_vector(self.translation) + next.translation,
+        }
+    }
+
+    pub fn apply_point(&self, p: Point3) -> Point3 {
+        self.apply_vector(p) + self.translation
+    }
+
+    pub fn apply_vector(&self, v: Vec3) -> Vec3 {
+        mul_vec(self.linear, v)
+    }
+
+    // Normals go through the inverse transpose so they stay perpendicular to scaled surfaces.
+    // The result isn't normalized.
+    pub fn apply_normal(&self, n: Vec3) -> Vec3 {
+        mul_vec(transpose(self.inverse), n)
+    }
+
+    pub fn inverse_point(&self, p: Point3) -> Point3 {
+        mul_vec(self.inverse, p - self.translation)
+    }
+
+    pub fn inverse_vector(&self, v: Vec3) -> Vec3 {
+        mul_vec(self.inverse, v)
+    }
+}
+
+fn diagonal(d: Vec3) -> [[f64; 3]; 3] {
+    [[d.x, 0.0, 0.0], [0.0, d.y, 0.0], [0.0, 0.0, d.z]]
+}
+
+fn transpose(m: [[f64; 3]; 3]) -> [[f64; 3]; 3] {
+    let mut t = [[0.0; 3]; 3];
+    for (i, row) in m.iter().enumerate() {
+        for (j, &value) in row.iter().enumerate() {
+            t[j][i] = value;
+        }
+    }
+    t
+}
+
+fn multiply(a: [[f64; 3]; 3], b: [[f64; 3]; 3]) -> [[f64; 3]; 3] {
+    let mut product = [[0.0; 3]; 3];
+    for (i, row) in product.iter_mut().enumerate() {
+        for (j, value) in row.iter_mut().enumerate() {
+            *value = (0..3).map(|k| a[i][k] * b[k][j]).sum();
+        }
+    }
+    product
+}
+
+fn mul_vec(m: [[f64; 3]; 3], v: Vec3) -> Vec3 {
+    let row = |r: [f64; 3]| r[0] * v.x + r[1] * v.y + r[2] * v.z;
+    Vec3::new(row(m[0]), row(m[1]), row(m[2]))
+}
+
+// Places a shared object in the scene with its own transform. The object is only referenced,
+// so a thousand instances of one heavy model cost one model plus a thousand transforms. Wrap
+// an instance in WithMaterial to give a copy its own look.
+
+pub struct Instance {
+    object: Arc<dyn Hittable>,
+    transform: Transform,
+}
+
+impl Instance {
+    pub fn new(object: Arc<dyn Hittable>, transform: Transform) -> Instance {
+        Instance { object, transform }
+    }
+
+    pub fn object(&self) -> &Arc<dyn Hittable> {
+        &self.object
+    }
+
+    pub fn transform(&self) -> Transform {
+        self.transform
+    }
+
+    // `r` taken into object space. The direction isn't renormalized, so t means the same thing
+    // in both spaces.
+    fn object_ray(&self, r: Ray) -> Ray {
+        Ray::new(
+            self.transform.inverse_point(r.origin),
+            self.transform.inverse_vector(r.direction),
+        )
+    }
+}
+
+impl Hittable for Instance {
+    fn hit(&self, r: Ray, t_min: f64, t_max: f64, rec: &mut HitRecord) -> bool {
+        match self.object.hit2(self.object_ray(r), t_min, t_max) {
+            Some(object_rec) => {
+                let outward_normal = if object_rec.front_face {
+                    object_rec.normal
+                } else {
+                    -object_rec.normal
+                };
+
+                *rec = object_rec;
+                rec.p = self.transform.apply_point(rec.p);
+                // The inverse transpose keeps the outward side outward, even for mirroring
+                let normal = Vec3::unit_vector(self.transform.apply_normal(outward_normal));
+                rec.set_face_normal(r, normal);
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn hit_any(&self, r: Ray, t_min: f64, t_max: f64) -> bool {
+        self.object.hit_any(self.object_ray(r), t_min, t_max)
+    }
+
+    // Box around the transformed corners of the object's box, which may be looser than a box
+    // fitted to the transformed object itself
+    fn bounding_box(&self) -> Option<Aabb> {
+        let object_box = self.object.bounding_box()?;
+        let (min, max) = (object_box.min(), object_box.max());
+        let corners = (0..8).map(|i| {
+            let pick = |bit: usize, low: f64, high: f64| if i & bit == 0 { low } else { high };
+            Point3::new(
+                pick(1, min.x, max.x),
+                pick(2, min.y, max.y),
+                pick(4, min.z, max.z),
+            )
+        });
+        Some(
+            corners
+                .map(|corner| self.transform.apply_point(corner))
+                .map(|corner| Aabb::new(corner, corner))
+                .fold(Aabb::empty(), Aabb::surrounding),
+        )
+    }
+
+    // Copies the shared object as well, so the copy no longer shares it with other instances
+    fn clone_box(&self) -> Arc<dyn Hittable> {
+        Arc::new(Instance::new(self.object.clone_box(), self.transform))
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
@@ -0,0 +1,66 @@
+pub mod aabb;
+pub mod bvh;
+pub mod camera;
+pub mod camera_path;
+pub mod coverage;
+pub mod denoise;
+pub mod environment;
+pub mod error;
+pub mod hittable;
+pub mod instance;
+mod json;
+pub mod material;
+pub mod obj;
+pub mod onb;
+pub mod optics;
+pub mod output;
+pub mod preview;
+pub mod progressive;
+pub mod ray;
+pub mod render;
+pub mod scene;
+pub mod scene_file;
+pub mod sky;
+pub mod spectral;
+pub mod sphere;
+mod supersample;
+pub mod tonemap;
+pub mod triangle;
+pub mod utility;
+pub mod vec3;
+
+pub use aabb::Aabb;
+pub use bvh::BvhNode;
+pub use camera::{Camera, Lens, Projection};
+pub use camera_path::{CameraPath, Interpolation, Keyframe};
+pub use coverage::{refine_coverage, CoverageSettings};
+pub use denoise::{denoise, DenoiseSettings};
+pub use environment::EnvironmentMap;
+pub use error::Error;
+pub use hittable::{FlipFace, HitRecord, Hittable, HittableList, WithId, WithMaterial};
+pub use instance::{Instance, Transform};
+pub use material::{
+    Dielectric, DiffuseLight, DiffuseLobe, FresnelModel, Lambertian, Material, Metal,
+};
+pub use obj::{load_obj, parse_obj};
+pub use onb::Onb;
+pub use output::{render_to_sink, save_image, write_to_sink, FileSink, OutputSink};
+pub use preview::{render_edge_preview, EdgePreviewSettings};
+pub use progressive::{render_progressive, render_with_budget, ProgressiveRender};
+pub use ray::Ray;
+pub use render::{
+    accumulate, encode_color, id_color, luminance, pick, pick_pixel, ray_color, render,
+    render_compact, render_with_aovs, write_color, write_color_with_gamma, Aovs, Background,
+    ClampMode, CompactColor, Crop, GammaMode, NormalSpace, PixelFilter, RenderSettings, UpAxis,
+};
+pub use scene::{poisson_scene, random_scene, scene_by_name, sphereflake_scene};
+pub use scene_file::Scene;
+pub use sky::PhysicalSky;
+pub use spectral::SpectralSettings;
+pub use sphere::Sphere;
+pub use tonemap::{apply_auto_exposure, exposure_scale, tonemap_and_encode, AutoExposure};
+pub use triangle::Triangle;
+pub use utility::{
+    clamp, degrees_to_radians, random_float, random_float_range, seed_random, with_rng,
+};
+pub use vec3::{cross, dot, Color, Point3, Vec3};
@@ -1,721 +1,38 @@
-use rand::Rng;
-use std::fs::File;
-use std::io::prelude::*;
-use std::ops::*;
-use std::rc::Rc;
-
-#[derive(Copy, Clone)]
-struct Vec3 {
-    x: f64,
-    y: f64,
-    z: f64,
-}
-
-impl Default for Vec3 {
-    fn default() -> Vec3 {
-        Vec3 {
-            x: 0.0,
-            y: 0.0,
-            z: 0.0,
-        }
-    }
-}
-
-impl Neg for Vec3 {
-    type Output = Self;
-    fn neg(self) -> Self {
-        Self {
-            x: -self.x,
-            y: -self.y,
-            z: -self.z,
-        }
-    }
-}
-
-impl Add for Vec3 {
-    type Output = Self;
-    fn add(self, rhs: Self) -> Self {
-        Self {
-            x: self.x + rhs.x,
-            y: self.y + rhs.y,
-            z: self.z + rhs.z,
-        }
-    }
-}
-
-// If having problems with multiplying, remember original code had two definitions that were
-// redundant, but maybe actually necessary.
-
-impl Mul<f64> for Vec3 {
-    type Output = Self;
-    fn mul(self, t: f64) -> Self {
-        Self {
-            x: self.x * t,
-            y: self.y * t,
-            z: self.z * t,
-        }
-    }
-}
-
-impl Mul<Vec3> for f64 {
-    type Output = Vec3;
-    fn mul(self, rhs: Vec3) -> Vec3 {
-        Vec3 {
-            x: rhs.x * self,
-            y: rhs.y * self,
-            z: rhs.z * self,
-        }
-    }
-}
-
-impl Mul<Vec3> for Vec3 {
-    type Output = Self;
-    fn mul(self, rhs: Self) -> Self {
-        Self {
-            x: self.x * rhs.x,
-            y: self.y * rhs.y,
-            z: self.z * rhs.z,
-        }
-    }
-}
-
-impl Div<Vec3> for Vec3 {
-    type Output = Self;
-    fn div(self, rhs: Self) -> Self {
-        Self {
-            x: self.x / rhs.x,
-            y: self.y / rhs.y,
-            z: self.z / rhs.z,
-        }
-    }
-}
-
-impl Div<f64> for Vec3 {
-    type Output = Self;
-    fn div(self, t: f64) -> Self {
-        Self {
-            x: self.x / t,
-            y: self.y / t,
-            z: self.z / t,
-        }
-    }
-}
-
-impl Sub for Vec3 {
-    type Output = Self;
-    fn sub(self, rhs: Self) -> Self {
-        Self {
-            x: self.x - rhs.x,
-            y: self.y - rhs.y,
-            z: self.z - rhs.z,
-        }
-    }
-}
-
-impl AddAssign<Vec3> for Vec3 {
-    fn add_assign(&mut self, rhs: Self) {
-        *self = Self {
-            x: self.x + rhs.x,
-            y: self.y + rhs.y,
-            z: self.z + rhs.z,
-        }
-    }
-}
-
-impl MulAssign<f64> for Vec3 {
-    fn mul_assign(&mut self, t: f64) {
-        *self = Self {
-            x: self.x * t,
-            y: self.y * t,
-            z: self.z * t,
-        }
-    }
-}
-
-impl DivAssign<f64> for Vec3 {
-    fn div_assign(&mut self, t: f64) {
-        *self = Self {
-            x: self.x / t,
-            y: self.y / t,
-            z: self.z / t,
-        }
-    }
-}
-
-fn cross(u: Vec3, v: Vec3) -> Vec3 {
-    Vec3 {
-        x: u.y * v.z - u.z * v.y,
-        y: u.z * v.x - u.x * v.z,
-        z: u.x * v.y - u.y * v.x,
-    }
-}
-
-fn dot(u: Vec3, v: Vec3) -> f64 {
-    u.x * v.x + u.y * v.y + u.z * v.z
-}
-
-impl Vec3 {
-    fn new(x: f64, y: f64, z: f64) -> Vec3 {
-        Vec3 { x, y, z }
-    }
-
-    fn near_zero(&self) -> bool {
-        let s = 1e-8;
-        (self.x.abs() < s) && (self.y.abs() < s) && (self.z.abs() < s)
-    }
-
-    fn random() -> Vec3 {
-        Vec3 {
-            x: random_float(),
-            y: random_float(),
-            z: random_float(),
-        }
-    }
-
-    fn random_range(min: f64, max: f64) -> Vec3 {
-        Vec3 {
-            x: random_float_range(min, max),
-            y: random_float_range(min, max),
-            z: random_float_range(min, max),
-        }
-    }
-
-    fn random_in_unit_sphere() -> Vec3 {
-        loop {
-            let p = Self::random_range(-1.0, 1.0);
-            if p.length_squared() >= 1.0 {
-                continue;
-            }
-            return p;
-        }
-    }
-
-    fn random_unit_vector() -> Vec3 {
-        Self::unit_vector(Self::random_in_unit_sphere())
-    }
-
-    fn random_in_unit_disk() -> Vec3 {
-        loop {
-            let p = Vec3::new(
-                random_float_range(-1.0, 1.0),
-                random_float_range(-1.0, 1.0),
-                0.0,
-            );
-            if p.length_squared() >= 1.0 {
-                continue;
-            } else {
-                return p;
-            }
-        }
-    }
-
-    fn unit_vector(vector: Vec3) -> Vec3 {
-        vector / vector.length()
-    }
-
-    fn length(self) -> f64 {
-        self.length_squared().sqrt()
-        // eprintln!("length value {}", x);
-        // x
-    }
-    fn length_squared(self) -> f64 {
-        (self.x * self.x) + (self.y * self.y) + (self.z * self.z)
-    }
-}
-
-type Point3 = Vec3;
-type Color = Vec3;
-
-// Ray stuff
-
-#[derive(Copy, Clone)]
-#[derive(Default)]
-struct Ray {
-    origin: Point3,
-    direction: Vec3,
-}
-
-impl Ray {
-    fn at(self, t: f64) -> Point3 {
-        self.origin + (self.direction * t)
-    }
-    fn new(origin: Point3, direction: Vec3) -> Ray {
-        Ray {
-            origin,
-            direction,
-        }
-    }
-}
-
-
-
-// Hit record
-#[derive(Clone)]
-struct HitRecord {
-    p: Point3,
-    normal: Vec3,
-    mat_ptr: Rc<dyn Material>,
-    t: f64,
-    front_face: bool,
-}
-
-impl HitRecord {
-    fn set_face_normal(&mut self, r: Ray, outward_normal: Vec3) {
-        if dot(r.direction, outward_normal) < 0.0 {
-            self.front_face = true;
-            self.normal = outward_normal;
-        } else {
-            self.front_face = false;
-            self.normal = -outward_normal;
-        }
-    }
-}
-
-impl Default for HitRecord {
-    fn default() -> HitRecord {
-        HitRecord {
-            p: Vec3::default(),
-            normal: Vec3::default(),
-            mat_ptr: Rc::new(Metal::new(Color::new(0.7, 0.3, 0.3), 1.0)),
-            t: 0.0,
-            front_face: false,
-        }
-    }
-}
-
-// Hittable trait and its associated functions
-
-trait Hittable {
-    fn hit(&self, r: Ray, t_min: f64, t_max: f64, rec: &mut HitRecord) -> bool;
-}
-struct Sphere {
-    center: Point3,
-    radius: f64,
-    mat_ptr: Rc<dyn Material>,
-}
-
-impl Sphere {
-    fn new(center: Point3, radius: f64, material: Rc<dyn Material>) -> Sphere {
-        Sphere {
-            center,
-            radius,
-            mat_ptr: material,
-        }
-    }
-}
-
-impl Hittable for Sphere {
-    fn hit(&self, r: Ray, t_min: f64, t_max: f64, rec: &mut HitRecord) -> bool {
-        let oc = r.origin - self.center;
-        let a = r.direction.length_squared();
-        let half_b = dot(oc, r.direction);
-        let c = oc.length_squared() - self.radius * self.radius;
-
-        let discriminant = half_b * half_b - a * c;
-        if discriminant < 0.0 {
-            return false;
-        }
-        let sqrtd = discriminant.sqrt();
-
-        // Find the closest root that is within the acceptable range
-
-        let mut root = (-half_b - sqrtd) / a;
-        if root < t_min || t_max < root {
-            root = (-half_b + sqrtd) / a;
-            if root < t_min || t_max < root {
-                return false;
-            }
-        }
-
-        rec.t = root;
-        rec.p = r.at(rec.t);
-        let outward_normal = (rec.p - self.center) / self.radius;
-        rec.set_face_normal(r, outward_normal);
-        rec.mat_ptr = self.mat_ptr.clone();
-
-        true
-    }
-}
-
-// List storing hittable objects
-
-struct HittableList {
-    objects: Vec<Rc<dyn Hittable>>,
-}
-
-impl HittableList {
-    fn new() -> HittableList {
-        HittableList {
-            objects: Vec::new(),
-        }
-    }
-
-    //fn clear(&mut self) {
-    //    self.objects.clear();
-    //}
-
-    fn add(&mut self, object: Rc<dyn Hittable>) {
-        self.objects.push(object);
-    }
-}
-
-impl Hittable for HittableList {
-    fn hit(&self, r: Ray, t_min: f64, t_max: f64, rec: &mut HitRecord) -> bool {
-        let mut temp_rec = HitRecord::default();
-        let mut hit_anything = false;
-        let mut closest_so_far = t_max;
-
-        for object in &self.objects {
-            if (*object).hit(r, t_min, closest_so_far, &mut temp_rec) {
-                hit_anything = true;
-                closest_so_far = temp_rec.t;
-                *rec = temp_rec.clone();
-            }
-        }
-
-        hit_anything
-    }
-}
-
-// Material time
-
-trait Material {
-    fn scatter(
-        &self,
-        r_in: Ray,
-        rec: HitRecord,
-        attenuation: &mut Color,
-        scattered: &mut Ray,
-    ) -> bool;
-}
-
-fn reflect(v: Vec3, n: Vec3) -> Vec3 {
-    v - n * dot(v, n) * 2.0
-}
-
-fn refract(uv: Vec3, n: Vec3, etai_over_etat: f64) -> Vec3 {
-    let cos_theta = dot(-uv, n).min(1.0);
-    let r_out_perp = (uv + n * cos_theta) * etai_over_etat;
-    let r_out_parallel = -(1.0 - r_out_perp.length_squared()).abs().sqrt() * n;
-    r_out_perp + r_out_parallel
-}
-
-#[derive(Copy, Clone)]
-struct Lambertian {
-    albedo: Color,
-}
-
-impl Lambertian {
-    fn new(albedo: Color) -> Lambertian {
-        Lambertian { albedo }
-    }
-}
-
-impl Material for Lambertian {
-    fn scatter(
-        &self,
-        _r_in: Ray,
-        rec: HitRecord,
-        attenuation: &mut Color,
-        scattered: &mut Ray,
-    ) -> bool {
-        let mut scatter_direction = rec.normal + Vec3::random_unit_vector();
-
-        // Catch degenerate scatter direction
-        if scatter_direction.near_zero() {
-            scatter_direction = rec.normal;
-        }
-
-        *scattered = Ray::new(rec.p, scatter_direction);
-        *attenuation = self.albedo;
-        true
-    }
-}
-
-#[derive(Copy, Clone)]
-struct Metal {
-    albedo: Color,
-    fuzz: f64,
-}
-
-impl Metal {
-    fn new(albedo: Color, fuzz: f64) -> Metal {
-        Metal {
-            albedo,
-            fuzz,
-        }
-    }
-}
-
-impl Material for Metal {
-    fn scatter(
-        &self,
-        r_in: Ray,
-        rec: HitRecord,
-        attenuation: &mut Color,
-        scattered: &mut Ray,
-    ) -> bool {
-        let reflected = reflect(Vec3::unit_vector(r_in.direction), rec.normal);
-
-        *scattered = Ray::new(rec.p, reflected + Vec3::random_in_unit_sphere() * self.fuzz);
-        *attenuation = self.albedo;
-        dot(scattered.direction, rec.normal) > 0.0
-    }
-}
-
-struct Dielectric {
-    ir: f64,
-}
-
-impl Dielectric {
-    fn new(ir: f64) -> Dielectric {
-        Dielectric { ir }
-    }
-    fn reflectance(cosine: f64, ref_idx: f64) -> f64 {
-        let mut r0 = (1.0 - ref_idx) / (1.0 + ref_idx);
-        r0 = r0 * r0;
-        r0 + (1.0 - r0) * (1.0 - cosine).powi(5)
-    }
-}
-
-impl Material for Dielectric {
-    fn scatter(
-        &self,
-        r_in: Ray,
-        rec: HitRecord,
-        attenuation: &mut Color,
-        scattered: &mut Ray,
-    ) -> bool {
-        let refraction_ratio = if rec.front_face {
-            1.0 / self.ir
-        } else {
-            self.ir
-        };
-        let unit_direction = Vec3::unit_vector(r_in.direction);
-        let cos_theta = dot(-unit_direction, rec.normal).min(1.0);
-        let sin_theta = (1.0 - cos_theta * cos_theta).sqrt();
-
-        let cannot_refract: bool = refraction_ratio * sin_theta > 1.0;
-        let direction = if cannot_refract || Dielectric::reflectance(cos_theta, refraction_ratio) > random_float() {
-            reflect(unit_direction, rec.normal)
-        } else {
-            refract(unit_direction, rec.normal, refraction_ratio)
-        };
-
-        *scattered = Ray::new(rec.p, direction);
-        *attenuation = Color::new(1.0, 1.0, 1.0);
-        true
-    }
-}
-
-// Utility functions
-
-// Clamp, keeps a value within a desired range
-fn clamp(x: f64, min: f64, max: f64) -> f64 {
-    if x < min {
-        return min;
-    }
-    if x > max {
-        max
-    } else {
-        x
-    }
-}
-
-fn random_float() -> f64 {
-    let mut rng = rand::thread_rng();
-    rng.gen::<f64>()
-}
-
-fn random_float_range(min: f64, max: f64) -> f64 {
-    let mut rng = rand::thread_rng();
-    rng.gen_range(min..max)
-}
-
-fn degrees_to_radians(degrees: f64) -> f64 {
-    degrees * std::f64::consts::PI / 180.0
-}
-
-// This is basically the pixel output function
-fn write_color(append_string: &mut String, pixel_color: Color, samples_per_pixel: i64) {
-    let mut r = pixel_color.x;
-    let mut g = pixel_color.y;
-    let mut b = pixel_color.z;
-
-    let scale = 1.0 / (samples_per_pixel as f64);
-
-    r = f64::sqrt(scale * r);
-    g = f64::sqrt(scale * g);
-    b = f64::sqrt(scale * b);
-
-    let output = format!(
-        "{} {} {}\n",
-        (256.0 * clamp(r, 0.0, 0.999)) as i64,
-        (256.0 * clamp(g, 0.0, 0.999)) as i64,
-        (256.0 * clamp(b, 0.0, 0.999)) as i64,
-    );
-    append_string.push_str(&output);
-}
-
-// Ray color thing
-fn ray_color(r: Ray, world: &dyn Hittable, depth: i64) -> Color {
-    let mut rec = HitRecord::default();
-
-    if depth <= 0 {
-        return Vec3::default();
-    }
-    if world.hit(r, 0.001, std::f64::INFINITY, &mut rec) {
-        let mut scattered = Ray::default();
-        let mut attenuation = Color::default();
-
-        if rec
-            .mat_ptr
-            .scatter(r, rec.clone(), &mut attenuation, &mut scattered)
-        {
-            return attenuation * ray_color(scattered, world, depth - 1);
-        }
-
-        return Color::default();
-
-        // let target = rec.p + rec.normal + Vec3::random_unit_vector();
-        // return ray_color(Ray::new(rec.p, target - rec.p), world, depth - 1) * 0.5;
-        //return (rec.normal + Vec3::new(1.0, 1.0, 1.0)) * 0.5;
-    }
-    let unit_direction = Vec3::unit_vector(r.direction);
-    let t = (unit_direction.y + 1.0) * 0.5;
-    (Vec3::new(1.0, 1.0, 1.0) * (1.0 - t)) + (Vec3::new(0.5, 0.7, 1.0) * t)
-}
-
-#[derive(Copy, Clone)]
-struct Camera {
-    origin: Point3,
-    horizontal: Vec3,
-    vertical: Vec3,
-    lower_left_corner: Vec3,
-    lens_radius: f64,
-    u: Vec3,
-    v: Vec3,
-}
-
-impl Camera {
-    fn new(
-        lookfrom: Point3,
-        lookat: Point3,
-        vup: Vec3,
-        vfov: f64,
-        aspect_ratio: f64,
-        aperture: f64,
-        focus_dist: f64,
-    ) -> Camera {
-        let theta = degrees_to_radians(vfov);
-        let h = (theta / 2.0).tan();
-        let viewport_height = 2.0 * h;
-        let viewport_width = aspect_ratio * viewport_height;
-
-        let w = Vec3::unit_vector(lookfrom - lookat);
-        let u = Vec3::unit_vector(cross(vup, w));
-        let v = cross(w, u);
-
-        let origin = lookfrom;
-        let horizontal = focus_dist * viewport_width * u;
-        let vertical = focus_dist * viewport_height * v;
-        let lower_left_corner = origin - (horizontal / 2.0) - (vertical / 2.0) - focus_dist * w;
-
-        let lens_radius = aperture / 2.0;
-        Camera {
-            origin,
-            horizontal,
-            vertical,
-            lower_left_corner,
-            lens_radius,
-            u,
-            v,
-        }
-    }
-
-    fn get_ray(self, s: f64, t: f64) -> Ray {
-        let rd = self.lens_radius * Vec3::random_in_unit_disk();
-        let offset = self.u * rd.x + self.v * rd.y;
-        Ray::new(
-            self.origin + offset,
-            self.lower_left_corner + s * self.horizontal + t * self.vertical - self.origin - offset,
-        )
-    }
-}
-
-fn random_scene() -> HittableList {
-    let mut world = HittableList::new();
-
-    let ground_material = Rc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
-    world.add(Rc::new(Sphere::new(
-        Point3::new(0.0, -1000.0, 0.0),
-        1000.0,
-        ground_material,
-    )));
-
-    for a in -11..11 {
-        for b in -11..11 {
-            let choose_mat = random_float();
-            let center = Point3::new(
-                a as f64 + 0.9 * random_float(),
-                0.2,
-                b as f64 + 0.9 * random_float(),
-            );
-
-            if (center - Point3::new(4.0, 0.2, 0.0)).length() > 0.9 {
-                let sphere_material: Rc<dyn Material>;
-
-                if choose_mat < 0.8 {
-                    // Diffuse
-                    let albedo = Color::random() * Color::random();
-                    sphere_material = Rc::new(Lambertian::new(albedo));
-                    world.add(Rc::new(Sphere::new(center, 0.2, sphere_material)));
-                } else if choose_mat < 0.95 {
-                    // Metal
-                    let albedo = Color::random_range(0.5, 1.0);
-                    let fuzz = random_float_range(0.0, 0.5);
-                    sphere_material = Rc::new(Metal::new(albedo, fuzz));
-                    world.add(Rc::new(Sphere::new(center, 0.2, sphere_material)));
-                } else {
-                    // Glass
-                    sphere_material = Rc::new(Dielectric::new(1.5));
-                    world.add(Rc::new(Sphere::new(center, 0.2, sphere_material)));
-                }
-            }
-        }
-    }
-
-    let material1 = Rc::new(Dielectric::new(1.5));
-    world.add(Rc::new(Sphere::new(
-        Point3::new(0.0, 1.0, 0.0),
-        1.0,
-        material1,
-    )));
-
-    let material2 = Rc::new(Lambertian::new(Color::new(0.4, 0.2, 0.1)));
-    world.add(Rc::new(Sphere::new(
-        Point3::new(-4.0, 1.0, 0.0),
-        1.0,
-        material2,
-    )));
-
-    let material3 = Rc::new(Metal::new(Color::new(0.7, 0.6, 0.5), 0.0));
-    world.add(Rc::new(Sphere::new(
-        Point3::new(4.0, 1.0, 0.0),
-        1.0,
-        material3,
-    )));
-
-    world
-}
+use rusty_ray_tracer::*;
+use std::env;
+use std::process;
+use std::sync::Arc;
 
 fn main() {
     // Image
-    let aspect_ratio = 16.0 / 9.0;
-    let image_width: i64 = 2560;
-    let image_height = (image_width as f64 / aspect_ratio) as i64;
-    let samples_per_pixel = 500;
-    let max_depth = 50;
+    let mut settings = RenderSettings::from_env().unwrap_or_else(|err| {
+        eprintln!("{}", err);
+        process::exit(1);
+    });
+    settings
+        .apply_args(env::args().skip(1))
+        .unwrap_or_else(|err| {
+            eprintln!("{}", err);
+            process::exit(1);
+        });
+    if let Some(crop) = settings.clamp_crop() {
+        let clamped = settings.region();
+        eprintln!(
+            "Warning: crop {} {} {} {} is outside the {}x{} image, using {} {} {} {}",
+            crop.x0,
+            crop.y0,
+            crop.x1,
+            crop.y1,
+            settings.image_width,
+            settings.image_height(),
+            clamped.x0,
+            clamped.y0,
+            clamped.x1,
+            clamped.y1
+        );
+    }
+    let aspect_ratio = settings.pixel_aspect_ratio();
+    let samples_per_pixel = settings.samples_per_pixel;
 
     // World
 
@@ -723,33 +40,64 @@ fn main() {
 
     //let mut world = HittableList::new();
 
-    let world = random_scene();
+    // Scene files bring their own camera and background, the camera replaces the one set up
+    // below
+    let (world, scene_camera) = if settings.scene.ends_with(".json") {
+        let scene = Scene::load(&settings.scene, aspect_ratio).unwrap_or_else(|err| {
+            eprintln!("{}", err);
+            process::exit(1);
+        });
+        settings.background = scene.background;
+        (scene.world, Some(scene.camera))
+    } else if settings.scene.ends_with(".obj") {
+        let material = Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+        let mesh = load_obj(&settings.scene, material).unwrap_or_else(|err| {
+            eprintln!("{}", err);
+            process::exit(1);
+        });
+        (mesh, None)
+    } else {
+        let world = scene_by_name(&settings.scene).unwrap_or_else(|| {
+            eprintln!("Unknown scene '{}'", settings.scene);
+            process::exit(1);
+        });
+        (world, None)
+    };
+
+    //let material_left = Arc::new(Lambertian::new(Color::new(0.0, 0.0, 1.0)));
+    //let material_right = Arc::new(Lambertian::new(Color::new(1.0, 0.0, 0.0)));
 
-    //let material_left = Rc::new(Lambertian::new(Color::new(0.0, 0.0, 1.0)));
-    //let material_right = Rc::new(Lambertian::new(Color::new(1.0, 0.0, 0.0)));
+    //world.add(Arc::new(Sphere::new(Point3::new(-R, 0.0, -1.0), R, material_left)));
+    //world.add(Arc::new(Sphere::new(Point3::new(R, 0.0, -1.0), R, material_right)));
 
-    //world.add(Rc::new(Sphere::new(Point3::new(-R, 0.0, -1.0), R, material_left)));
-    //world.add(Rc::new(Sphere::new(Point3::new(R, 0.0, -1.0), R, material_right)));
+    //let material_ground = Arc::new(Lambertian::new(Color::new(0.8, 0.8, 0.0)));
+    //let material_center = Arc::new(Lambertian::new(Color::new(0.1, 0.2, 0.5)));
+    //let material_left = Arc::new(Dielectric::new(1.5));
+    //let material_right = Arc::new(Metal::new(Color::new(0.8, 0.6, 0.2), 0.0));
 
-    //let material_ground = Rc::new(Lambertian::new(Color::new(0.8, 0.8, 0.0)));
-    //let material_center = Rc::new(Lambertian::new(Color::new(0.1, 0.2, 0.5)));
-    //let material_left = Rc::new(Dielectric::new(1.5));
-    //let material_right = Rc::new(Metal::new(Color::new(0.8, 0.6, 0.2), 0.0));
+    //world.add(Arc::new(Sphere::new(Point3::new(0.0, -100.5, -1.0), 100.0, material_ground)));
+    //world.add(Arc::new(Sphere::new(Point3::new(0.0, 0.0, -1.0), 0.5, material_center)));
+    //world.add(Arc::new(Sphere::new(Point3::new(-1.0, 0.0, -1.0), 0.5, material_left.clone())));
+    //world.add(Arc::new(Sphere::new(Point3::new(-1.0, 0.0, -1.0), -0.4, material_left)));
+    //world.add(Arc::new(Sphere::new(Point3::new(1.0, 0.0, -1.0), 0.5, material_right)));
 
-    //world.add(Rc::new(Sphere::new(Point3::new(0.0, -100.5, -1.0), 100.0, material_ground)));
-    //world.add(Rc::new(Sphere::new(Point3::new(0.0, 0.0, -1.0), 0.5, material_center)));
-    //world.add(Rc::new(Sphere::new(Point3::new(-1.0, 0.0, -1.0), 0.5, material_left.clone())));
-    //world.add(Rc::new(Sphere::new(Point3::new(-1.0, 0.0, -1.0), -0.4, material_left)));
-    //world.add(Rc::new(Sphere::new(Point3::new(1.0, 0.0, -1.0), 0.5, material_right)));
+    // Same hits as the plain list, but each ray only tests the spheres near it
+    let world = BvhNode::new(world.iter().cloned().collect());
 
     // Camera
 
     let lookfrom = Point3::new(13.0, 2.0, 3.0);
     let lookat = Point3::new(0.0, 0.0, 0.0);
-    let vup = Vec3::new(0.0, 1.0, 0.0);
-    //let dist_to_focus = (lookfrom - lookat).length();
+    let vup = settings.up_axis.vector();
     let dist_to_focus = 10.0;
+    // Set to focus on lookat instead of dist_to_focus
+    let autofocus = false;
     let aperture = 0.1;
+    let lens_tilt = 0.0;
+    Camera::check_view(lookfrom, lookat, vup).unwrap_or_else(|err| {
+        eprintln!("{}", err);
+        process::exit(1);
+    });
     let cam = Camera::new(
         lookfrom,
         lookat,
@@ -758,29 +106,27 @@ fn main() {
         aspect_ratio,
         aperture,
         dist_to_focus,
-    );
+    )
+    .with_autofocus(autofocus)
+    .with_lens_tilt(lens_tilt);
+    let cam = scene_camera.unwrap_or(cam);
 
     // Render
-    let mut file_string = format!("P3\n{} {}\n255\n", image_width, image_height);
-
-    for draw_height in (0..image_height).rev() {
-        eprintln!("\rScanlines remaining: {} ", draw_height);
-
-        for draw_width in 0..image_width {
-            let mut pixel_color: Color = Vec3::default();
-
-            for _ in 0..samples_per_pixel {
-                let u = (draw_width as f64 + random_float()) / (image_width as f64 - 1.0);
-                let v = (draw_height as f64 + random_float()) / (image_height as f64 - 1.0);
-                let r = cam.get_ray(u, v);
-
-                pixel_color += ray_color(r, &world, max_depth);
-            }
-
-            write_color(&mut file_string, pixel_color, samples_per_pixel);
+    let mut pixels = match settings.denoise {
+        Some(denoise_settings) => {
+            let (pixels, aovs) = render_with_aovs(&world, &cam, &settings);
+            denoise(&pixels, &aovs, &settings, &denoise_settings)
         }
+        None => render(&world, &cam, &settings),
+    };
+
+    if let Some(exposure) = &settings.auto_exposure {
+        apply_auto_exposure(&mut pixels, samples_per_pixel, exposure);
     }
-    let mut file =
-        File::create("/home/edthelegendary/Pictures/Wallpapers/2kRayTracerWallpaper.ppm").unwrap();
-    file.write_all(file_string.as_bytes()).unwrap();
+
+    // The extension picks the format, .hdr or .pfm keep the linear radiance
+    save_image(&settings.output, &pixels, &settings).unwrap_or_else(|err| {
+        eprintln!("{}", err);
+        process::exit(1);
+    });
 }
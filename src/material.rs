@@ -0,0 +1,847 @@
+use crate::hittable::HitRecord;
+use crate::onb::Onb;
+use crate::optics::{fresnel_dielectric, schlick_reflectance};
+use crate::ray::Ray;
+use crate::spectral::rgb_to_spectrum;
+use crate::utility::clamp;
+use crate::vec3::{cross, dot, Color, Point3, Vec3};
+use rand::{Rng, RngCore};
+use std::any::Any;
+use std::f64::consts::PI;
+use std::sync::Arc;
+
+// Still reachable from here, they lived in this module before optics existed
+pub use crate::optics::{reflect, refract};
+
+// Material time
+
+pub trait Material: Send + Sync {
+    // All randomness has to come from `rng`, which makes scattering repeatable given a seeded
+    // generator. The renderer passes in the thread's generator through with_rng, so calling
+    // random_float here would panic on the double borrow.
+    fn scatter(
+        &self,
+        r_in: Ray,
+        rec: HitRecord,
+        attenuation: &mut Color,
+        scattered: &mut Ray,
+        rng: &mut dyn RngCore,
+    ) -> bool;
+
+    // Single-wavelength version of scatter for the spectral renderer, with `wavelength` in
+    // nanometres. The default scatters as usual and reads the RGB attenuation back at that
+    // wavelength, only materials whose behaviour depends on it (dispersive glass) override it.
+    fn scatter_spectral(
+        &self,
+        r_in: Ray,
+        rec: HitRecord,
+        wavelength: f64,
+        attenuation: &mut f64,
+        scattered: &mut Ray,
+        rng: &mut dyn RngCore,
+    ) -> bool {
+        let mut color = Color::default();
+        let scatters = self.scatter(r_in, rec, &mut color, scattered, rng);
+        *attenuation = rgb_to_spectrum(color, wavelength);
+        scatters
+    }
+
+    // BSDF times the cosine for a bounce from `rec` towards `direction`, together with the
+    // density per unit solid angle with which scatter picks that direction. The renderer uses
+    // it to weigh in directions sampled towards settings.lights. None for materials that only
+    // scatter into a few sharp directions (mirrors, glass), which aren't light sampled.
+    fn bsdf(&self, _r_in: Ray, _rec: &HitRecord, _direction: Vec3) -> Option<(Color, f64)> {
+        None
+    }
+
+    // Light given off by the surface itself, added whether or not the ray also scatters. `rec`
+    // is the hit being shaded, so one-sided emitters can check front_face.
+    fn emitted(&self, _rec: &HitRecord) -> Color {
+        Color::default()
+    }
+
+    // True for mirror-like materials, whose reflections go to the specular AOV
+    fn is_specular(&self) -> bool {
+        false
+    }
+
+    // Independent copy, for editing a material without affecting everything that shares it
+    fn clone_box(&self) -> Arc<dyn Material>;
+
+    // Lets tools recover the concrete material with downcast_ref / downcast_mut
+    fn as_any(&self) -> &dyn Any;
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+}
+
+// Nudges a scattered ray's origin off the surface along the normal, to the side `direction`
+// leaves on (outwards for reflections, inwards for refractions), so the ray can't hit the
+// surface it starts on again. The nudge grows with the distance from the origin because that
+// is how floating point error in `p` grows too.
+pub fn offset_origin(p: Point3, normal: Vec3, direction: Vec3) -> Point3 {
+    let magnitude = p.abs().max_component();
+    let offset = 1e-6 * (1.0 + magnitude);
+    if dot(direction, normal) > 0.0 {
+        p + normal * offset
+    } else {
+        p - normal * offset
+    }
+}
+
+// How Lambertian picks its bounce direction. All of them estimate the same diffuse surface,
+// each sample is weighted by brdf * cos / pdf, so they only differ in how quickly the noise
+// goes away.
+#[derive(Copy, Clone, PartialEq)]
+pub enum DiffuseLobe {
+    // normal + random_unit_vector, the book's method. Also cosine distributed.
+    UnitVector,
+    // Cosine-weighted hemisphere sampling through an Onb, pdf cos / pi
+    Cosine,
+    // Every direction in the hemisphere equally likely, pdf 1 / (2 pi)
+    UniformHemisphere,
+    // Directions bunched around the normal like cos^exponent, pdf (n + 1) / (2 pi) cos^n.
+    // Exponents above 1 undersample grazing directions and get noisier there.
+    Phong { exponent: f64 },
+}
+
+impl DiffuseLobe {
+    // Density of sampling a direction at `cosine` to the normal, per unit solid angle
+    pub fn pdf(self, cosine: f64) -> f64 {
+        if cosine <= 0.0 {
+            return 0.0;
+        }
+        match self {
+            DiffuseLobe::UnitVector | DiffuseLobe::Cosine => cosine / PI,
+            DiffuseLobe::UniformHemisphere => 1.0 / (2.0 * PI),
+            DiffuseLobe::Phong { exponent } => {
+                (exponent + 1.0) / (2.0 * PI) * cosine.powf(exponent)
+            }
+        }
+    }
+
+    // Direction around +z drawn from the lobe, for the lobes that sample through an Onb
+    fn sample_local(self, rng: &mut dyn RngCore) -> Vec3 {
+        let cos_theta = match self {
+            DiffuseLobe::UniformHemisphere => rng.gen::<f64>(),
+            DiffuseLobe::Phong { exponent } => rng.gen::<f64>().powf(1.0 / (exponent + 1.0)),
+            _ => return Vec3::random_cosine_direction_from(rng),
+        };
+        let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+        let phi = 2.0 * PI * rng.gen::<f64>();
+        Vec3::new(sin_theta * phi.cos(), sin_theta * phi.sin(), cos_theta)
+    }
+}
+
+#[derive(Copy, Clone)]
+pub struct Lambertian {
+    albedo: Color,
+    lobe: DiffuseLobe,
+}
+
+impl Lambertian {
+    pub fn new(albedo: Color) -> Lambertian {
+        Lambertian {
+            albedo,
+            lobe: DiffuseLobe::UnitVector,
+        }
+    }
+
+    pub fn with_lobe(mut self, lobe: DiffuseLobe) -> Lambertian {
+        self.lobe = lobe;
+        self
+    }
+
+    pub fn lobe(&self) -> DiffuseLobe {
+        self.lobe
+    }
+}
+
+impl Material for Lambertian {
+    fn scatter(
+        &self,
+        _r_in: Ray,
+        rec: HitRecord,
+        attenuation: &mut Color,
+        scattered: &mut Ray,
+        rng: &mut dyn RngCore,
+    ) -> bool {
+        if self.lobe != DiffuseLobe::UnitVector {
+            let direction = Onb::build_from_w(rec.normal).local_vec(self.lobe.sample_local(rng));
+            let cosine = dot(direction, rec.normal);
+            let pdf = self.lobe.pdf(cosine);
+            if pdf <= 0.0 {
+                return false;
+            }
+
+            *scattered = Ray::new(offset_origin(rec.p, rec.normal, direction), direction);
+            *attenuation = self.albedo * (cosine / PI / pdf);
+            return true;
+        }
+
+        let mut scatter_direction = rec.normal + Vec3::random_unit_vector_from(rng);
+
+        // Catch degenerate scatter direction
+        if scatter_direction.near_zero() {
+            scatter_direction = rec.normal;
+        }
+
+        *scattered = Ray::new(
+            offset_origin(rec.p, rec.normal, scatter_direction),
+            scatter_direction,
+        );
+        *attenuation = self.albedo;
+        true
+    }
+
+    fn bsdf(&self, _r_in: Ray, rec: &HitRecord, direction: Vec3) -> Option<(Color, f64)> {
+        let cosine = dot(Vec3::unit_vector(direction), rec.normal);
+        if cosine <= 0.0 {
+            return Some((Color::default(), 0.0));
+        }
+        Some((self.albedo * (cosine / PI), self.lobe.pdf(cosine)))
+    }
+
+    fn clone_box(&self) -> Arc<dyn Material> {
+        Arc::new(*self)
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+#[derive(Copy, Clone)]
+pub struct Metal {
+    albedo: Color,
+    fuzz: f64,
+}
+
+impl Metal {
+    pub fn new(albedo: Color, fuzz: f64) -> Metal {
+        Metal {
+            albedo,
+            fuzz,
+        }
+    }
+
+    pub fn fuzz(&self) -> f64 {
+        self.fuzz
+    }
+
+    pub fn set_fuzz(&mut self, fuzz: f64) {
+        self.fuzz = fuzz;
+    }
+}
+
+impl Material for Metal {
+    fn scatter(
+        &self,
+        r_in: Ray,
+        rec: HitRecord,
+        attenuation: &mut Color,
+        scattered: &mut Ray,
+        rng: &mut dyn RngCore,
+    ) -> bool {
+        let reflected = reflect(Vec3::unit_vector(r_in.direction), rec.normal);
+
+        let mut direction = reflected + Vec3::random_in_unit_sphere_from(rng) * self.fuzz;
+
+        // Catch degenerate scatter direction, fuzz of 1 or more can cancel the reflection out
+        if direction.near_zero() {
+            direction = reflected;
+        }
+
+        *scattered = Ray::new(offset_origin(rec.p, rec.normal, direction), direction);
+        *attenuation = self.albedo;
+        dot(scattered.direction, rec.normal) > 0.0
+    }
+
+    fn is_specular(&self) -> bool {
+        true
+    }
+
+    fn clone_box(&self) -> Arc<dyn Material> {
+        Arc::new(*self)
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+// How a Dielectric works out the share of light it reflects
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum FresnelModel {
+    // Schlick's approximation, cheap but a little off at grazing angles for high IORs
+    Schlick,
+    // The full Fresnel equations, averaging s- and p-polarized light
+    Exact,
+}
+
+#[derive(Copy, Clone)]
+pub struct Dielectric {
+    ir: f64,
+    roughness: f64,
+    emission: Color,
+    fresnel: FresnelModel,
+    dispersion: f64,
+    // Beer-Lambert absorption coefficients per unit of distance travelled inside
+    absorption: Color,
+}
+
+impl Dielectric {
+    pub fn new(ir: f64) -> Dielectric {
+        Dielectric {
+            ir,
+            roughness: 0.0,
+            emission: Color::default(),
+            fresnel: FresnelModel::Schlick,
+            dispersion: 0.0,
+            absorption: Color::default(),
+        }
+    }
+
+    // Colors the glass by absorbing light on its way through, so that `color` is what is left
+    // of white light after `distance` units inside. Thick parts come out darker and more
+    // saturated than thin ones. It applies to everything seen through the glass, the sky
+    // included, since the loss is taken when the ray leaves the glass.
+    pub fn with_absorption(mut self, color: Color, distance: f64) -> Dielectric {
+        let coefficient = |c: f64| -c.clamp(1e-6, 1.0).ln() / distance.max(1e-6);
+        self.absorption = Color::new(
+            coefficient(color.x),
+            coefficient(color.y),
+            coefficient(color.z),
+        );
+        self
+    }
+
+    // Makes the IOR depend on the wavelength following Cauchy's equation, with `dispersion`
+    // as its B coefficient in square micrometres (about 0.0042 for crown glass, 0.01 for
+    // dense flint). `ir` stays the IOR at the sodium D line, 589.3nm. Only the spectral
+    // renderer can show the effect, the RGB one always uses `ir`.
+    pub fn with_dispersion(mut self, dispersion: f64) -> Dielectric {
+        self.dispersion = dispersion;
+        self
+    }
+
+    // IOR at `wavelength` nanometres
+    pub fn ior_at(&self, wavelength: f64) -> f64 {
+        let micrometres = wavelength / 1000.0;
+        let d_line = 0.5893;
+        self.ir + self.dispersion * (1.0 / (micrometres * micrometres) - 1.0 / (d_line * d_line))
+    }
+
+    // Schlick is the default, Exact is worth it for things like diamond (IOR 2.42)
+    pub fn with_fresnel(mut self, fresnel: FresnelModel) -> Dielectric {
+        self.fresnel = fresnel;
+        self
+    }
+
+    // Makes the glass glow with `emission` while it keeps reflecting and refracting, like a
+    // neon tube
+    pub fn with_emission(mut self, emission: Color) -> Dielectric {
+        self.emission = emission;
+        self
+    }
+
+    // Frosts the surface. Roughness runs from 0 (clear glass) to 1 and is squared into the GGX
+    // alpha, so the blur grows roughly linearly with it.
+    pub fn with_roughness(mut self, roughness: f64) -> Dielectric {
+        self.roughness = clamp(roughness, 0.0, 1.0);
+        self
+    }
+
+    pub fn reflectance(cosine: f64, ref_idx: f64) -> f64 {
+        schlick_reflectance(cosine, ref_idx)
+    }
+
+    // Exact unpolarized Fresnel reflectance. `ref_idx` is the ratio of the IOR on the incoming
+    // side to the IOR on the far side, same as for reflectance, and total internal reflection
+    // gives 1.
+    pub fn reflectance_exact(cosine: f64, ref_idx: f64) -> f64 {
+        fresnel_dielectric(cosine, ref_idx)
+    }
+}
+
+impl Dielectric {
+    // Length of the path `r_in` just took through the glass, or 0 if it came from outside
+    fn distance_inside(&self, r_in: Ray, rec: &HitRecord) -> f64 {
+        if rec.front_face {
+            0.0
+        } else {
+            rec.t * r_in.direction.length()
+        }
+    }
+
+    // The body of scatter, for an IOR that may differ from self.ir. Returns the factor the
+    // bounce is weighted by, or None if the ray is lost.
+    fn scatter_with_ior(
+        &self,
+        ir: f64,
+        r_in: Ray,
+        rec: HitRecord,
+        scattered: &mut Ray,
+        rng: &mut dyn RngCore,
+    ) -> Option<f64> {
+        let refraction_ratio = if rec.front_face {
+            1.0 / ir
+        } else {
+            ir
+        };
+        let unit_direction = Vec3::unit_vector(r_in.direction);
+
+        // Rough glass reflects and refracts about a randomly tilted microfacet instead of the
+        // surface normal. Only facets visible from the incoming ray are sampled, the check below
+        // just guards against rounding at grazing angles.
+        let mut facet_normal = rec.normal;
+        let alpha = self.roughness * self.roughness;
+        if self.roughness > 0.0 {
+            let sampled = sample_ggx_visible_normal(rec.normal, -unit_direction, alpha, rng);
+            if dot(-unit_direction, sampled) > 0.0 {
+                facet_normal = sampled;
+            }
+        }
+
+        let cos_theta = dot(-unit_direction, facet_normal).min(1.0);
+        let sin_theta = (1.0 - cos_theta * cos_theta).sqrt();
+
+        // One lobe is picked with the Fresnel probability, so the two always add up to the
+        // incoming energy
+        let cannot_refract: bool = refraction_ratio * sin_theta > 1.0;
+        let reflectance = match self.fresnel {
+            FresnelModel::Schlick => Dielectric::reflectance(cos_theta, refraction_ratio),
+            FresnelModel::Exact => Dielectric::reflectance_exact(cos_theta, refraction_ratio),
+        };
+        let reflects = cannot_refract || reflectance > rng.gen::<f64>();
+        let direction = if reflects {
+            reflect(unit_direction, facet_normal)
+        } else {
+            refract(unit_direction, facet_normal, refraction_ratio)
+        };
+
+        // A tilted facet can send the ray to the wrong side of the actual surface
+        if self.roughness > 0.0 && (dot(direction, rec.normal) > 0.0) != reflects {
+            return None;
+        }
+
+        *scattered = Ray::new(offset_origin(rec.p, rec.normal, direction), direction);
+
+        // Sampling visible facets already accounts for masking towards the viewer, what is left
+        // is the chance that the outgoing ray is shadowed by another facet on its way out. This
+        // darkens rough glass at grazing angles instead of letting it glow at the rim.
+        if self.roughness > 0.0 {
+            Some(smith_g1(rec.normal, direction, alpha))
+        } else {
+            Some(1.0)
+        }
+    }
+}
+
+impl Material for Dielectric {
+    fn scatter(
+        &self,
+        r_in: Ray,
+        rec: HitRecord,
+        attenuation: &mut Color,
+        scattered: &mut Ray,
+        rng: &mut dyn RngCore,
+    ) -> bool {
+        let distance = self.distance_inside(r_in, &rec);
+        *attenuation = Color::new(
+            (-self.absorption.x * distance).exp(),
+            (-self.absorption.y * distance).exp(),
+            (-self.absorption.z * distance).exp(),
+        );
+        match self.scatter_with_ior(self.ir, r_in, rec, scattered, rng) {
+            Some(weight) => {
+                *attenuation *= weight;
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn scatter_spectral(
+        &self,
+        r_in: Ray,
+        rec: HitRecord,
+        wavelength: f64,
+        attenuation: &mut f64,
+        scattered: &mut Ray,
+        rng: &mut dyn RngCore,
+    ) -> bool {
+        let distance = self.distance_inside(r_in, &rec);
+        let transmittance = (-rgb_to_spectrum(self.absorption, wavelength) * distance).exp();
+        match self.scatter_with_ior(self.ior_at(wavelength), r_in, rec, scattered, rng) {
+            Some(weight) => {
+                *attenuation = transmittance * weight;
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn emitted(&self, _rec: &HitRecord) -> Color {
+        self.emission
+    }
+
+    fn is_specular(&self) -> bool {
+        true
+    }
+
+    fn clone_box(&self) -> Arc<dyn Material> {
+        Arc::new(*self)
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+// Area light that glows from its front face only, so a light panel doesn't also light up
+// whatever is behind it. Wrap the geometry in FlipFace to make it shine the other way.
+
+#[derive(Copy, Clone)]
+pub struct DiffuseLight {
+    emit: Color,
+}
+
+impl DiffuseLight {
+    pub fn new(emit: Color) -> DiffuseLight {
+        DiffuseLight { emit }
+    }
+}
+
+impl Material for DiffuseLight {
+    fn scatter(
+        &self,
+        _r_in: Ray,
+        _rec: HitRecord,
+        _attenuation: &mut Color,
+        _scattered: &mut Ray,
+        _rng: &mut dyn RngCore,
+    ) -> bool {
+        false
+    }
+
+    fn emitted(&self, rec: &HitRecord) -> Color {
+        if rec.front_face {
+            self.emit
+        } else {
+            Color::default()
+        }
+    }
+
+    fn clone_box(&self) -> Arc<dyn Material> {
+        Arc::new(*self)
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+// Smith masking function for GGX with roughness `alpha`: the share of the surface around
+// normal `n` that can be seen from direction `w`, on either side of the surface
+fn smith_g1(n: Vec3, w: Vec3, alpha: f64) -> f64 {
+    let cos_theta = dot(Vec3::unit_vector(w), n).abs();
+    if cos_theta <= 0.0 {
+        return 0.0;
+    }
+    let tan_squared = (1.0 - cos_theta * cos_theta).max(0.0) / (cos_theta * cos_theta);
+    2.0 / (1.0 + (1.0 + alpha * alpha * tan_squared).sqrt())
+}
+
+// Draws a microfacet normal from the part of the GGX distribution with roughness `alpha` that
+// `view` can actually see, following Heitz, "Sampling the GGX Distribution of Visible Normals"
+// (2018). Plain NDF sampling wastes many samples on back-facing facets, especially at grazing
+// angles and high roughness.
+fn sample_ggx_visible_normal(n: Vec3, view: Vec3, alpha: f64, rng: &mut dyn RngCore) -> Vec3 {
+    // Any pair of axes perpendicular to n will do
+    let helper = if n.x.abs() > 0.9 {
+        Vec3::new(0.0, 1.0, 0.0)
+    } else {
+        Vec3::new(1.0, 0.0, 0.0)
+    };
+    let tangent = Vec3::unit_vector(cross(helper, n));
+    let bitangent = cross(n, tangent);
+
+    // Stretch the view direction so the rough surface becomes a hemisphere of unit roughness
+    let local_view = Vec3::new(dot(view, tangent), dot(view, bitangent), dot(view, n));
+    let stretched = Vec3::unit_vector(Vec3::new(
+        alpha * local_view.x,
+        alpha * local_view.y,
+        local_view.z,
+    ));
+
+    // Orthonormal basis around the stretched view direction
+    let length_squared = stretched.x * stretched.x + stretched.y * stretched.y;
+    let t1 = if length_squared > 0.0 {
+        Vec3::new(-stretched.y, stretched.x, 0.0) / length_squared.sqrt()
+    } else {
+        Vec3::new(1.0, 0.0, 0.0)
+    };
+    let t2 = cross(stretched, t1);
+
+    // Uniform point on a disk, squashed onto the visible half of it
+    let r = rng.gen::<f64>().sqrt();
+    let phi = 2.0 * std::f64::consts::PI * rng.gen::<f64>();
+    let p1 = r * phi.cos();
+    let s = 0.5 * (1.0 + stretched.z);
+    let p2 = (1.0 - s) * (1.0 - p1 * p1).sqrt() + s * r * phi.sin();
+
+    // Project back up onto the hemisphere and undo the stretch
+    let up = (1.0 - p1 * p1 - p2 * p2).max(0.0).sqrt();
+    let hemisphere_normal = t1 * p1 + t2 * p2 + stretched * up;
+    let local_normal = Vec3::unit_vector(Vec3::new(
+        alpha * hemisphere_normal.x,
+        alpha * hemisphere_normal.y,
+        hemisphere_normal.z.max(0.0),
+    ));
+
+    tangent * local_normal.x + bitangent * local_normal.y + n * local_normal.z
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::mock::StepRng;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    // Hit at the origin on a surface facing +y
+    fn record(front_face: bool) -> HitRecord {
+        HitRecord {
+            p: Point3::default(),
+            normal: Vec3::new(0.0, 1.0, 0.0),
+            mat_ptr: Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5))),
+            t: 1.0,
+            front_face,
+            object_id: 0,
+        }
+    }
+
+    fn scatter(
+        material: &dyn Material,
+        r_in: Ray,
+        rec: HitRecord,
+        seed: u64,
+    ) -> (bool, Ray, Color) {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let mut attenuation = Color::default();
+        let mut scattered = Ray::default();
+        let scatters = material.scatter(r_in, rec, &mut attenuation, &mut scattered, &mut rng);
+        (scatters, scattered, attenuation)
+    }
+
+    fn same(a: Vec3, b: Vec3) -> bool {
+        a.x == b.x && a.y == b.y && a.z == b.z
+    }
+
+    #[test]
+    fn lambertian_scatters_into_the_normals_hemisphere() {
+        let r_in = Ray::new(Point3::new(0.0, 1.0, 1.0), Vec3::new(0.0, -1.0, -1.0));
+        let lobes = [
+            DiffuseLobe::UnitVector,
+            DiffuseLobe::Cosine,
+            DiffuseLobe::UniformHemisphere,
+            DiffuseLobe::Phong { exponent: 8.0 },
+        ];
+        for lobe in lobes {
+            let material = Lambertian::new(Color::new(0.5, 0.5, 0.5)).with_lobe(lobe);
+            for seed in 0..200 {
+                let (scatters, scattered, _) = scatter(&material, r_in, record(true), seed);
+                assert!(scatters);
+                assert!(dot(scattered.direction, Vec3::new(0.0, 1.0, 0.0)) > 0.0);
+                assert!(scattered.origin.y > 0.0);
+            }
+        }
+    }
+
+    #[test]
+    fn smooth_metal_gives_the_mirror_direction() {
+        let material = Metal::new(Color::new(0.8, 0.8, 0.8), 0.0);
+        let r_in = Ray::new(Point3::new(-1.0, 1.0, 0.0), Vec3::new(1.0, -1.0, 0.0));
+        let mirror = Vec3::unit_vector(Vec3::new(1.0, 1.0, 0.0));
+
+        for seed in 0..20 {
+            let (scatters, scattered, attenuation) = scatter(&material, r_in, record(true), seed);
+            assert!(scatters);
+            assert!((scattered.direction - mirror).length() < 1e-12);
+            assert!(same(attenuation, Color::new(0.8, 0.8, 0.8)));
+        }
+    }
+
+    #[test]
+    fn dielectric_repeats_with_the_same_seed() {
+        let material = Dielectric::new(1.5);
+        let r_in = Ray::new(Point3::new(-1.0, 1.0, 0.0), Vec3::new(1.0, -1.0, 0.0));
+
+        for seed in 0..50 {
+            let (first_scatters, first, first_attenuation) =
+                scatter(&material, r_in, record(true), seed);
+            let (second_scatters, second, second_attenuation) =
+                scatter(&material, r_in, record(true), seed);
+            assert_eq!(first_scatters, second_scatters);
+            assert!(same(first.origin, second.origin));
+            assert!(same(first.direction, second.direction));
+            assert!(same(first_attenuation, second_attenuation));
+        }
+    }
+
+    #[test]
+    fn dielectric_reflects_past_the_critical_angle() {
+        // Leaving glass at 60 degrees to the normal, well past the critical angle of about 42
+        let material = Dielectric::new(1.5);
+        let direction = Vec3::new(60f64.to_radians().sin(), -60f64.to_radians().cos(), 0.0);
+        let r_in = Ray::new(Point3::default() - direction, direction);
+
+        let mirror = reflect(direction, record(false).normal);
+
+        for seed in 0..50 {
+            let (scatters, scattered, _) = scatter(&material, r_in, record(false), seed);
+            assert!(scatters);
+            assert!((scattered.direction - mirror).length() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn schlick_and_exact_fresnel_for_diamond() {
+        // Air into diamond, IOR 2.42
+        let ratio = 1.0 / 2.42;
+        let at = |degrees: f64| {
+            let cosine = degrees.to_radians().cos();
+            (
+                Dielectric::reflectance(cosine, ratio),
+                Dielectric::reflectance_exact(cosine, ratio),
+            )
+        };
+
+        // Identical head on, ((n - 1) / (n + 1))^2 = 0.172
+        let (schlick, exact) = at(0.0);
+        assert!((schlick - exact).abs() < 1e-12);
+        assert!((exact - 0.1724).abs() < 1e-4);
+
+        // Close up to 45 degrees
+        for degrees in [15.0, 30.0, 45.0] {
+            let (schlick, exact) = at(degrees);
+            assert!((schlick - exact).abs() < 0.01, "{} degrees", degrees);
+        }
+
+        // Towards grazing Schlick overestimates diamond's reflectance by several percent
+        let (schlick, exact) = at(80.0);
+        assert!(schlick - exact > 0.05);
+
+        // Both reach total reflection at grazing incidence
+        let (schlick, exact) = at(90.0);
+        assert!((schlick - 1.0).abs() < 1e-9);
+        assert!((exact - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn cancelled_scatter_directions_fall_back() {
+        // A stuck generator makes the unit sphere sampler return (0, 0, 1), which cancels out
+        // against a normal or a mirror direction of (0, 0, -1)
+        let mut rec = record(true);
+        rec.normal = Vec3::new(0.0, 0.0, -1.0);
+        let r_in = Ray::new(Point3::new(0.0, 0.0, -1.0), Vec3::new(0.0, 0.0, 1.0));
+        let materials: [&dyn Material; 2] = [
+            &Lambertian::new(Color::new(0.5, 0.5, 0.5)),
+            &Metal::new(Color::new(0.8, 0.8, 0.8), 1.0),
+        ];
+        for material in materials {
+            let mut attenuation = Color::default();
+            let mut scattered = Ray::default();
+            let mut stuck = StepRng::new(u64::MAX, 0);
+            let scatters = material.scatter(
+                r_in,
+                rec.clone(),
+                &mut attenuation,
+                &mut scattered,
+                &mut stuck,
+            );
+            assert!(scatters);
+            assert!(same(scattered.direction, rec.normal));
+        }
+    }
+
+    #[test]
+    fn rough_glass_never_gains_energy() {
+        let normal = Vec3::new(0.0, 1.0, 0.0);
+        let grazing = Vec3::new(1.0, 0.1, 0.0);
+        let mut previous = f64::INFINITY;
+        for alpha in [0.0, 0.1, 0.3, 0.6, 1.0] {
+            let g1 = smith_g1(normal, grazing, alpha);
+            assert!(g1 <= 1.0 && g1 < previous + 1e-12, "alpha {}", alpha);
+            previous = g1;
+        }
+        assert_eq!(smith_g1(normal, grazing, 0.0), 1.0);
+
+        // Average throughput at 60 degrees: smooth glass keeps it all, rougher glass loses some
+        let theta = 60f64.to_radians();
+        let r_in = Ray::new(Point3::default(), Vec3::new(theta.sin(), -theta.cos(), 0.0));
+        let mut previous = f64::INFINITY;
+        for roughness in [0.0, 0.3, 0.6, 1.0] {
+            let material = Dielectric::new(1.5).with_roughness(roughness);
+            let count = 4000;
+            let mut total = 0.0;
+            for seed in 0..count {
+                let (scatters, _, attenuation) = scatter(&material, r_in, record(true), seed);
+                if scatters {
+                    assert!(attenuation.max_component() <= 1.0);
+                    total += attenuation.x;
+                }
+            }
+            let mean = total / count as f64;
+            assert!(mean <= 1.0 && mean < previous + 0.01, "mean {}", mean);
+            if roughness == 0.0 {
+                assert_eq!(mean, 1.0);
+            }
+            previous = mean;
+        }
+    }
+
+    #[test]
+    fn scattered_origins_start_on_the_side_they_leave_by() {
+        let theta = 30f64.to_radians();
+        let r_in = Ray::new(Point3::default(), Vec3::new(theta.sin(), -theta.cos(), 0.0));
+        let (mut reflected, mut refracted) = (0, 0);
+        for p in [Point3::default(), Point3::new(1e6, 0.0, -1e6)] {
+            for front_face in [true, false] {
+                for seed in 0..200 {
+                    let mut rec = record(front_face);
+                    rec.p = p;
+                    let (scatters, scattered, _) = scatter(&Dielectric::new(1.5), r_in, rec, seed);
+                    assert!(scatters);
+                    if scattered.direction.y > 0.0 {
+                        assert!(scattered.origin.y > 0.0);
+                        reflected += 1;
+                    } else {
+                        assert!(scattered.origin.y < 0.0);
+                        refracted += 1;
+                    }
+                }
+            }
+        }
+
+        // Both lobes were exercised, leaving the glass at 30 degrees never reaches the critical
+        // angle
+        assert!(reflected > 0 && refracted > 0);
+    }
+}
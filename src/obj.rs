@@ -0,0 +1,99 @@
+use crate::error::Error;
+use crate::hittable::HittableList;
+use crate::material::Material;
+use crate::triangle::Triangle;
+use crate::vec3::Point3;
+use std::fs;
+use std::io;
+use std::sync::Arc;
+
+// Wavefront OBJ meshes. Only the vertex positions (v) and faces (f) are used: faces with more
+// than three corners are split into a fan of triangles, texture coordinates and normals in
+// the face corners (v/vt/vn) are skipped, and every other kind of line is ignored. Indices
+// start at 1, negative ones count back from the last vertex read so far.
+
+pub fn load_obj(path: &str, material: Arc<dyn Material>) -> Result<HittableList, Error> {
+    let text = fs::read_to_string(path).map_err(|err| {
+        Error::Io(io::Error::new(
+            err.kind(),
+            format!("Couldn't read '{}': {}", path, err),
+        ))
+    })?;
+
+    parse_obj(&text, material).map_err(|err| match err {
+        Error::SceneLoad(message) | Error::InvalidConfig(message) => {
+            Error::SceneLoad(format!("Couldn't load '{}': {}", path, message))
+        }
+        other => other,
+    })
+}
+
+// Same as load_obj, for OBJ text that is already in memory
+pub fn parse_obj(text: &str, material: Arc<dyn Material>) -> Result<HittableList, Error> {
+    let mut vertices: Vec<Point3> = Vec::new();
+    let mut mesh = HittableList::new();
+
+    for (index, line) in text.lines().enumerate() {
+        let error = |message: String| Error::SceneLoad(format!("line {}: {}", index + 1, message));
+        let line = line.split('#').next().unwrap_or("");
+        let mut fields = line.split_whitespace();
+
+        match fields.next() {
+            Some("v") => {
+                let mut coords = [0.0; 3];
+                for coord in coords.iter_mut() {
+                    *coord = match fields.next().map(str::parse::<f64>) {
+                        Some(Ok(value)) => value,
+                        _ => return Err(error(String::from("a vertex needs three numbers"))),
+                    };
+                }
+                vertices.push(Point3::new(coords[0], coords[1], coords[2]));
+            }
+            Some("f") => {
+                let corners = fields
+                    .map(|corner| vertex_index(corner, vertices.len()))
+                    .collect::<Result<Vec<usize>, String>>()
+                    .map_err(error)?;
+                if corners.len() < 3 {
+                    return Err(error(format!(
+                        "a face needs at least three vertices, got {}",
+                        corners.len()
+                    )));
+                }
+
+                for i in 1..corners.len() - 1 {
+                    mesh.add(Arc::new(Triangle::new(
+                        vertices[corners[0]],
+                        vertices[corners[i]],
+                        vertices[corners[i + 1]],
+                        material.clone(),
+                    )));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    Ok(mesh)
+}
+
+// Position in `vertices` for one face corner such as "3", "3/1", "3//2" or "-1/1/2"
+fn vertex_index(corner: &str, vertex_count: usize) -> Result<usize, String> {
+    let position = corner.split('/').next().unwrap_or("");
+    let index: i64 = position
+        .parse()
+        .map_err(|_| format!("'{}' isn't a vertex index", corner))?;
+
+    let resolved = if index < 0 {
+        vertex_count as i64 + index
+    } else {
+        index - 1
+    };
+    if index == 0 || resolved < 0 || resolved >= vertex_count as i64 {
+        return Err(format!(
+            "vertex {} doesn't exist, {} have been read so far",
+            index, vertex_count
+        ));
+    }
+    Ok(resolved as usize)
+}
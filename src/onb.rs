@@ -0,0 +1,35 @@
+use crate::vec3::{cross, Vec3};
+
+// Orthonormal basis, used to carry directions sampled around +z over to an arbitrary axis
+
+#[derive(Copy, Clone)]
+pub struct Onb {
+    pub u: Vec3,
+    pub v: Vec3,
+    pub w: Vec3,
+}
+
+impl Onb {
+    // Right-handed basis whose w axis points along `n`. The other two axes are an arbitrary
+    // perpendicular pair.
+    pub fn build_from_w(n: Vec3) -> Onb {
+        let w = Vec3::unit_vector(n);
+        let helper = if w.x.abs() > 0.9 {
+            Vec3::new(0.0, 1.0, 0.0)
+        } else {
+            Vec3::new(1.0, 0.0, 0.0)
+        };
+        let v = Vec3::unit_vector(cross(w, helper));
+        let u = cross(v, w);
+        Onb { u, v, w }
+    }
+
+    // World space direction for coordinates (a, b, c) along u, v and w
+    pub fn local(&self, a: f64, b: f64, c: f64) -> Vec3 {
+        self.u * a + self.v * b + self.w * c
+    }
+
+    pub fn local_vec(&self, a: Vec3) -> Vec3 {
+        self.local(a.x, a.y, a.z)
+    }
+}
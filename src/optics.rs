@@ -0,0 +1,140 @@
+use crate::vec3::{dot, Color, Vec3};
+
+// Low-level reflection and refraction math shared by the materials. Directions point along
+// the ray, so `v` and `uv` head into the surface, and `n` is the normal on the incoming side.
+// Cosines are taken against that normal, with relative IORs given as eta_incoming / eta_far.
+
+// Mirror reflection of `v` about `n`
+pub fn reflect(v: Vec3, n: Vec3) -> Vec3 {
+    v - n * dot(v, n) * 2.0
+}
+
+// Snell's law for the unit direction `uv`. Only meaningful when there is no total internal
+// reflection, see total_internal_reflection.
+pub fn refract(uv: Vec3, n: Vec3, etai_over_etat: f64) -> Vec3 {
+    let cos_theta = dot(-uv, n).min(1.0);
+    let r_out_perp = (uv + n * cos_theta) * etai_over_etat;
+    let r_out_parallel = -(1.0 - r_out_perp.length_squared()).abs().sqrt() * n;
+    r_out_perp + r_out_parallel
+}
+
+// True if light arriving at `cosine` can't leave into the far medium at all
+pub fn total_internal_reflection(cosine: f64, etai_over_etat: f64) -> bool {
+    etai_over_etat * etai_over_etat * (1.0 - cosine * cosine) > 1.0
+}
+
+// Schlick's approximation of the dielectric Fresnel reflectance. Note that it doesn't know
+// about total internal reflection, callers check that separately.
+pub fn schlick_reflectance(cosine: f64, etai_over_etat: f64) -> f64 {
+    let mut r0 = (1.0 - etai_over_etat) / (1.0 + etai_over_etat);
+    r0 = r0 * r0;
+    r0 + (1.0 - r0) * (1.0 - cosine).powi(5)
+}
+
+// Exact unpolarized Fresnel reflectance between two dielectrics, the average of the s and p
+// polarized terms. Total internal reflection gives 1, and the transmitted share is always
+// 1 minus this.
+pub fn fresnel_dielectric(cosine: f64, etai_over_etat: f64) -> f64 {
+    let sin_t_squared = etai_over_etat * etai_over_etat * (1.0 - cosine * cosine);
+    if sin_t_squared >= 1.0 {
+        return 1.0;
+    }
+    let cos_t = (1.0 - sin_t_squared).sqrt();
+
+    let r_s = (etai_over_etat * cosine - cos_t) / (etai_over_etat * cosine + cos_t);
+    let r_p = (cosine - etai_over_etat * cos_t) / (cosine + etai_over_etat * cos_t);
+    (r_s * r_s + r_p * r_p) / 2.0
+}
+
+// Unpolarized Fresnel reflectance of a conductor with complex IOR eta + i k, relative to the
+// medium the light comes from. Uses the usual closed form for air-to-metal interfaces, which
+// gives ((eta-1)^2 + k^2) / ((eta+1)^2 + k^2) at normal incidence and 1 at grazing angles.
+pub fn fresnel_conductor(cosine: f64, eta: f64, k: f64) -> f64 {
+    let cos2 = (cosine * cosine).min(1.0);
+    let sin2 = 1.0 - cos2;
+
+    let t0 = eta * eta - k * k - sin2;
+    let a2_plus_b2 = (t0 * t0 + 4.0 * eta * eta * k * k).sqrt();
+    let a = (0.5 * (a2_plus_b2 + t0)).max(0.0).sqrt();
+
+    let t1 = a2_plus_b2 + cos2;
+    let t2 = 2.0 * cosine * a;
+    let r_s = (t1 - t2) / (t1 + t2);
+
+    let t3 = cos2 * a2_plus_b2 + sin2 * sin2;
+    let t4 = t2 * sin2;
+    let r_p = r_s * (t3 - t4) / (t3 + t4);
+
+    (r_s + r_p) / 2.0
+}
+
+// fresnel_conductor for each color channel, with eta and k given per channel as measured for
+// real metals (e.g. gold is about eta = (0.18, 0.42, 1.37), k = (3.42, 2.35, 1.77))
+pub fn fresnel_conductor_rgb(cosine: f64, eta: Color, k: Color) -> Color {
+    Color::new(
+        fresnel_conductor(cosine, eta.x, k.x),
+        fresnel_conductor(cosine, eta.y, k.y),
+        fresnel_conductor(cosine, eta.z, k.z),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: Vec3, b: Vec3) -> bool {
+        (a - b).length() < 1e-12
+    }
+
+    #[test]
+    fn reflect_flips_the_normal_component() {
+        let n = Vec3::new(0.0, 1.0, 0.0);
+        let reflected = reflect(Vec3::new(1.0, -2.0, 3.0), n);
+        assert!(close(reflected, Vec3::new(1.0, 2.0, 3.0)));
+    }
+
+    #[test]
+    fn refract_follows_snells_law() {
+        let n = Vec3::new(0.0, 1.0, 0.0);
+
+        // Head on, nothing bends
+        let straight = refract(Vec3::new(0.0, -1.0, 0.0), n, 1.0 / 1.5);
+        assert!(close(straight, Vec3::new(0.0, -1.0, 0.0)));
+
+        // 30 degrees from air into glass, sin(theta_t) = sin(30) / 1.5 = 1/3
+        let theta = 30f64.to_radians();
+        let uv = Vec3::new(theta.sin(), -theta.cos(), 0.0);
+        let refracted = refract(uv, n, 1.0 / 1.5);
+        assert!((refracted.length() - 1.0).abs() < 1e-12);
+        assert!((refracted.x - 1.0 / 3.0).abs() < 1e-12);
+        assert!(refracted.y < 0.0);
+    }
+
+    #[test]
+    fn total_internal_reflection_past_the_critical_angle() {
+        // Leaving glass, the critical angle is asin(1 / 1.5), about 41.8 degrees
+        let cosine = |degrees: f64| degrees.to_radians().cos();
+        assert!(!total_internal_reflection(cosine(40.0), 1.5));
+        assert!(total_internal_reflection(cosine(44.0), 1.5));
+        assert!(!total_internal_reflection(cosine(89.0), 1.0 / 1.5));
+        assert_eq!(fresnel_dielectric(cosine(44.0), 1.5), 1.0);
+    }
+
+    #[test]
+    fn fresnel_models_agree_head_on_and_at_grazing() {
+        let ratio = 1.0 / 1.5;
+        let r0 = 0.04;
+        assert!((schlick_reflectance(1.0, ratio) - r0).abs() < 1e-12);
+        assert!((fresnel_dielectric(1.0, ratio) - r0).abs() < 1e-12);
+
+        assert!((schlick_reflectance(0.0, ratio) - 1.0).abs() < 1e-12);
+        assert!((fresnel_dielectric(0.0, ratio) - 1.0).abs() < 1e-12);
+        assert!(fresnel_dielectric(0.01, ratio) > 0.9);
+
+        // Gold-like metal: ((eta - 1)^2 + k^2) / ((eta + 1)^2 + k^2) head on, 1 at grazing
+        let (eta, k) = (0.18, 3.42);
+        let head_on = ((eta - 1.0) * (eta - 1.0) + k * k) / ((eta + 1.0) * (eta + 1.0) + k * k);
+        assert!((fresnel_conductor(1.0, eta, k) - head_on).abs() < 1e-9);
+        assert!((fresnel_conductor(0.0, eta, k) - 1.0).abs() < 1e-9);
+    }
+}
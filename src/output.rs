@@ -0,0 +1,326 @@
+use crate::camera::Camera;
+use crate::error::Error;
+use crate::hittable::Hittable;
+use crate::render::{
+    encode_color, render, render_rows, write_color_with_gamma, Crop, GammaMode, RenderSettings,
+};
+use crate::vec3::Color;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+// Destination for finished pixels, e.g. an image file, a network stream or a texture upload.
+// `begin` gets the size of the image, then every pixel arrives exactly once through
+// `write_tile` as linear radiance (already divided by the sample count), rows top-down within
+// each tile. `rect` is in image coordinates, so (0, 0) is the top-left pixel of what `begin`
+// announced even for cropped renders.
+pub trait OutputSink {
+    fn begin(&mut self, width: usize, height: usize) -> Result<(), Error>;
+    fn write_tile(&mut self, rect: Crop, pixels: &[Color]) -> Result<(), Error>;
+    fn finish(&mut self) -> Result<(), Error>;
+}
+
+#[derive(Copy, Clone)]
+enum FileFormat {
+    Ppm,
+    Png,
+    Hdr,
+    Pfm,
+}
+
+// Collects tiles and writes them to an image file on `finish`, picking the format from the
+// extension:
+//   .ppm  8-bit P3, gamma corrected (see with_gamma) and clamped
+//   .png  8-bit RGB, encoded the same way as .ppm but binary and readable by everything
+//   .hdr  Radiance RGBE, linear and unclamped with about 1% precision per pixel
+//   .pfm  Portable float map, linear 32-bit floats, so values are kept exactly as rendered
+// The linear formats store the averaged radiance, with no tone mapping or gamma applied.
+pub struct FileSink {
+    path: String,
+    format: FileFormat,
+    gamma: GammaMode,
+    width: usize,
+    height: usize,
+    pixels: Vec<Color>,
+}
+
+impl FileSink {
+    // Fails right away if the extension isn't one of the supported formats
+    pub fn new(path: &str) -> Result<FileSink, Error> {
+        let extension = Path::new(path)
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .map(|ext| ext.to_ascii_lowercase());
+
+        let format = match extension.as_deref() {
+            Some("ppm") => FileFormat::Ppm,
+            Some("png") => FileFormat::Png,
+            Some("hdr") => FileFormat::Hdr,
+            Some("pfm") => FileFormat::Pfm,
+            Some("exr") => {
+                return Err(Error::InvalidConfig(String::from(
+                    "OpenEXR output isn't supported in this build, use .hdr or .pfm",
+                )))
+            }
+            _ => {
+                return Err(Error::InvalidConfig(format!(
+                    "Don't know how to write '{}', use .ppm, .png, .hdr or .pfm",
+                    path
+                )))
+            }
+        };
+
+        Ok(FileSink {
+            path: String::from(path),
+            format,
+            gamma: GammaMode::Gamma2,
+            width: 0,
+            height: 0,
+            pixels: Vec::new(),
+        })
+    }
+
+    // Transfer curve for .ppm and .png output, the linear formats ignore it
+    pub fn with_gamma(mut self, gamma: GammaMode) -> FileSink {
+        self.gamma = gamma;
+        self
+    }
+}
+
+impl OutputSink for FileSink {
+    fn begin(&mut self, width: usize, height: usize) -> Result<(), Error> {
+        self.width = width;
+        self.height = height;
+        self.pixels = vec![Color::default(); width * height];
+        Ok(())
+    }
+
+    fn write_tile(&mut self, rect: Crop, pixels: &[Color]) -> Result<(), Error> {
+        let tile_width = rect.width() as usize;
+        let fits = rect.x0 >= 0
+            && rect.y0 >= 0
+            && rect.x1 as usize <= self.width
+            && rect.y1 as usize <= self.height
+            && pixels.len() == tile_width * rect.height() as usize;
+        if !fits {
+            return Err(Error::InvalidConfig(format!(
+                "Tile {},{} to {},{} doesn't fit a {}x{} image",
+                rect.x0, rect.y0, rect.x1, rect.y1, self.width, self.height
+            )));
+        }
+
+        for (i, row) in pixels.chunks(tile_width.max(1)).enumerate() {
+            let start = (rect.y0 as usize + i) * self.width + rect.x0 as usize;
+            self.pixels[start..start + row.len()].copy_from_slice(row);
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<(), Error> {
+        let bytes = match self.format {
+            FileFormat::Ppm => {
+                let mut file_string = format!("P3\n{} {}\n255\n", self.width, self.height);
+                for &pixel_color in &self.pixels {
+                    write_color_with_gamma(&mut file_string, pixel_color, 1, self.gamma);
+                }
+                file_string.into_bytes()
+            }
+            FileFormat::Png => encode_png(&self.pixels, self.width, self.height, self.gamma),
+            FileFormat::Hdr => encode_hdr(&self.pixels, self.width, self.height),
+            FileFormat::Pfm => encode_pfm(&self.pixels, self.width, self.height),
+        };
+
+        fs::write(&self.path, bytes).map_err(|err| {
+            Error::Io(io::Error::new(
+                err.kind(),
+                format!("Couldn't write '{}': {}", self.path, err),
+            ))
+        })
+    }
+}
+
+// Writes a rendered buffer (per-pixel sums, laid out like render's output) to `path` through a
+// FileSink, see there for the formats
+pub fn save_image(path: &str, pixels: &[Color], settings: &RenderSettings) -> Result<(), Error> {
+    let mut sink = FileSink::new(path)?.with_gamma(settings.gamma);
+    write_to_sink(&mut sink, pixels, settings)
+}
+
+// Hands a finished buffer from render to `sink` as one tile
+pub fn write_to_sink(
+    sink: &mut dyn OutputSink,
+    pixels: &[Color],
+    settings: &RenderSettings,
+) -> Result<(), Error> {
+    let region = settings.region();
+    let scale = 1.0 / settings.samples_per_pixel as f64;
+    let average: Vec<Color> = pixels
+        .iter()
+        .map(|&pixel_color| pixel_color * scale)
+        .collect();
+
+    sink.begin(region.width() as usize, region.height() as usize)?;
+    sink.write_tile(Crop::new(0, 0, region.width(), region.height()), &average)?;
+    sink.finish()
+}
+
+// Renders straight into `sink`. Plain renders push each band of scanlines as soon as it's done,
+// modes that need the whole image first (supersampling, previews, time budgets and coverage
+// refinement) render as usual and hand the result over in one tile at the end.
+pub fn render_to_sink(
+    world: &dyn Hittable,
+    cam: &Camera,
+    settings: &RenderSettings,
+    sink: &mut dyn OutputSink,
+) -> Result<(), Error> {
+    let streamable = settings.ssaa <= 1
+        && settings.edge_preview.is_none()
+        && settings.time_budget.is_none()
+        && settings.coverage.is_none();
+    if !streamable {
+        let pixels = render(world, cam, settings);
+        return write_to_sink(sink, &pixels, settings);
+    }
+
+    let region = settings.region();
+    let scale = 1.0 / settings.samples_per_pixel as f64;
+    sink.begin(region.width() as usize, region.height() as usize)?;
+
+    // A few rows per thread at a time keeps every thread busy while still handing finished
+    // rows to the sink as they come
+    let band = (settings.threads.max(1) * 4) as i64;
+    let mut row = region.y0;
+    while row < region.y1 {
+        let rows = row..(row + band).min(region.y1);
+        let pixels = render_rows(world, cam, settings, rows.clone(), None);
+        let scaled: Vec<Color> = pixels.iter().map(|&pixel| pixel * scale).collect();
+
+        let y0 = rows.start - region.y0;
+        let y1 = rows.end - region.y0;
+        sink.write_tile(Crop::new(0, y0, region.width(), y1), &scaled)?;
+        row = rows.end;
+    }
+
+    sink.finish()
+}
+
+// 8-bit RGB PNG. The image data goes into zlib as stored (uncompressed) deflate blocks, which
+// keeps the encoder tiny at the cost of files about the size of the raw pixels. That is still
+// a fraction of the ASCII P3 for the same image.
+fn encode_png(pixels: &[Color], width: usize, height: usize, gamma: GammaMode) -> Vec<u8> {
+    // Every row starts with its filter type, 0 for none
+    let mut raw = Vec::with_capacity(height * (1 + width * 3));
+    for row in pixels.chunks(width.max(1)).take(height) {
+        raw.push(0);
+        for &pixel_color in row {
+            raw.extend_from_slice(&encode_color(pixel_color, 1, gamma));
+        }
+    }
+
+    let mut zlib = vec![0x78, 0x01];
+    let mut blocks = raw.chunks(0xffff).peekable();
+    if blocks.peek().is_none() {
+        zlib.extend_from_slice(&[1, 0, 0, 0xff, 0xff]);
+    }
+    while let Some(block) = blocks.next() {
+        let is_last = blocks.peek().is_none();
+        let length = block.len() as u16;
+        zlib.push(is_last as u8);
+        zlib.extend_from_slice(&length.to_le_bytes());
+        zlib.extend_from_slice(&(!length).to_le_bytes());
+        zlib.extend_from_slice(block);
+    }
+    zlib.extend_from_slice(&adler32(&raw).to_be_bytes());
+
+    let mut header = Vec::with_capacity(13);
+    header.extend_from_slice(&(width as u32).to_be_bytes());
+    header.extend_from_slice(&(height as u32).to_be_bytes());
+    // 8 bits per channel, RGB, deflate, standard filters, no interlacing
+    header.extend_from_slice(&[8, 2, 0, 0, 0]);
+
+    let mut bytes = vec![0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n'];
+    png_chunk(&mut bytes, b"IHDR", &header);
+    png_chunk(&mut bytes, b"IDAT", &zlib);
+    png_chunk(&mut bytes, b"IEND", &[]);
+    bytes
+}
+
+// Length, type, data and a CRC over the type and data
+fn png_chunk(bytes: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    bytes.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    let start = bytes.len();
+    bytes.extend_from_slice(kind);
+    bytes.extend_from_slice(data);
+    let crc = crc32(&bytes[start..]);
+    bytes.extend_from_slice(&crc.to_be_bytes());
+}
+
+// CRC-32 as PNG (and zip) use it, bit by bit since it only runs over each chunk once
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xffff_ffffu32;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xedb8_8320 & mask);
+        }
+    }
+    !crc
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for &byte in data {
+        a = (a + byte as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+    (b << 16) | a
+}
+
+// Flat (not run-length encoded) RGBE scanlines, top row first
+fn encode_hdr(pixels: &[Color], width: usize, height: usize) -> Vec<u8> {
+    let mut bytes = format!(
+        "#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y {} +X {}\n",
+        height, width
+    )
+    .into_bytes();
+
+    for &pixel_color in pixels {
+        bytes.extend_from_slice(&color_to_rgbe(pixel_color));
+    }
+    bytes
+}
+
+// Shared exponent encoding: the brightest channel sets a power of two and every channel keeps
+// an 8-bit mantissa relative to it. Negative and NaN channels come out as zero.
+fn color_to_rgbe(color: Color) -> [u8; 4] {
+    let brightest = color.max_component();
+    if brightest.is_nan() || brightest < 1e-32 {
+        return [0, 0, 0, 0];
+    }
+
+    // brightest = mantissa * 2^exponent with the mantissa in [0.5, 1)
+    let exponent = brightest.log2().floor() as i32 + 1;
+    let scale = 256.0 / 2f64.powi(exponent);
+    let channel = |value: f64| (value * scale).clamp(0.0, 255.0) as u8;
+    [
+        channel(color.x),
+        channel(color.y),
+        channel(color.z),
+        (exponent + 128).clamp(0, 255) as u8,
+    ]
+}
+
+// Little-endian PFM, which stores its rows bottom to top
+fn encode_pfm(pixels: &[Color], width: usize, height: usize) -> Vec<u8> {
+    let mut bytes = format!("PF\n{} {}\n-1.0\n", width, height).into_bytes();
+
+    for row in pixels.chunks(width.max(1)).rev().take(height) {
+        for &pixel_color in row {
+            for value in [pixel_color.x, pixel_color.y, pixel_color.z] {
+                bytes.extend_from_slice(&(value as f32).to_le_bytes());
+            }
+        }
+    }
+    bytes
+}
@@ -0,0 +1,104 @@
+use crate::camera::Camera;
+use crate::hittable::Hittable;
+use crate::render::{luminance, render_image, render_pixel, seed_row, Aovs, RenderSettings};
+use crate::vec3::Color;
+
+// Settings for the edge-driven preview. Every pixel gets a single sample, then only pixels that
+// sit on a luminance or normal discontinuity are super-sampled.
+
+#[derive(Copy, Clone)]
+pub struct EdgePreviewSettings {
+    // Luminance difference to a neighbor that marks a pixel as an edge
+    pub luminance_threshold: f64,
+    // Length of the difference between neighboring normals that marks a pixel as an edge
+    pub normal_threshold: f64,
+    // Samples taken for each edge pixel
+    pub edge_samples: i64,
+}
+
+impl Default for EdgePreviewSettings {
+    fn default() -> EdgePreviewSettings {
+        EdgePreviewSettings {
+            luminance_threshold: 0.5,
+            normal_threshold: 0.5,
+            edge_samples: 16,
+        }
+    }
+}
+
+// Two-pass preview render. The result is laid out like render's, with every pixel scaled as if
+// it had been sampled samples_per_pixel times, so it can go straight to write_color.
+pub fn render_edge_preview(
+    world: &dyn Hittable,
+    cam: &Camera,
+    settings: &RenderSettings,
+    preview: &EdgePreviewSettings,
+) -> Vec<Color> {
+    let image_height = settings.image_height();
+    let region = settings.region();
+    let samples_per_pixel = settings.samples_per_pixel as f64;
+
+    let mut coarse_settings = settings.clone();
+    coarse_settings.samples_per_pixel = 1;
+    coarse_settings.coverage = None;
+    let mut aovs = Aovs::default();
+    let coarse = render_image(world, cam, &coarse_settings, Some(&mut aovs));
+
+    let mut edge_settings = settings.clone();
+    edge_settings.samples_per_pixel = preview.edge_samples;
+    let edge_scale = samples_per_pixel / preview.edge_samples as f64;
+
+    let mut pixels = Vec::with_capacity(coarse.len());
+
+    for row in 0..region.height() {
+        // Runs on the calling thread, whichever threads rendered the coarse pass
+        seed_row(settings, 2, region.y0 + row);
+        for col in 0..region.width() {
+            let index = (row * region.width() + col) as usize;
+
+            if is_edge(&coarse, &aovs, settings, col, row, preview) {
+                // Buffers only cover the crop, rows are stored top-down but render_pixel takes
+                // full-frame coordinates counting up from the bottom
+                let draw_width = region.x0 + col;
+                let draw_height = image_height - 1 - (region.y0 + row);
+                let sum = render_pixel(world, cam, &edge_settings, draw_width, draw_height, None);
+                pixels.push(sum * edge_scale);
+            } else {
+                pixels.push(coarse[index] * samples_per_pixel);
+            }
+        }
+    }
+
+    pixels
+}
+
+// Compares a pixel of the single-sample pass with its four direct neighbors
+fn is_edge(
+    coarse: &[Color],
+    aovs: &Aovs,
+    settings: &RenderSettings,
+    col: i64,
+    row: i64,
+    preview: &EdgePreviewSettings,
+) -> bool {
+    let image_width = settings.region().width();
+    let image_height = settings.region().height();
+    let index = (row * image_width + col) as usize;
+    let neighbors = [
+        (col - 1, row),
+        (col + 1, row),
+        (col, row - 1),
+        (col, row + 1),
+    ];
+
+    neighbors.iter().any(|&(x, y)| {
+        if x < 0 || y < 0 || x >= image_width || y >= image_height {
+            return false;
+        }
+        let other = (y * image_width + x) as usize;
+        let luminance_diff = (luminance(coarse[index]) - luminance(coarse[other])).abs();
+        let normal_diff = (aovs.normal[index] - aovs.normal[other]).length();
+
+        luminance_diff > preview.luminance_threshold || normal_diff > preview.normal_threshold
+    })
+}
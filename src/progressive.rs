@@ -0,0 +1,143 @@
+use crate::camera::Camera;
+use crate::hittable::Hittable;
+use crate::render::{sample_pixel, RenderSettings};
+use crate::utility::seed_random;
+use crate::vec3::Color;
+use std::time::{Duration, Instant};
+
+// Running sums for rendering an image in passes of one sample per pixel, so a noisy image is
+// available right away and keeps improving for as long as passes are added. Passes run on the
+// calling thread whatever settings.threads says, which keeps a seeded pass the same draws in
+// the same order.
+
+pub struct ProgressiveRender {
+    sums: Vec<Color>,
+    weights: Vec<f64>,
+    passes: i64,
+}
+
+impl ProgressiveRender {
+    pub fn new(settings: &RenderSettings) -> ProgressiveRender {
+        let region = settings.region();
+        let pixel_count = (region.width() * region.height()) as usize;
+        ProgressiveRender {
+            sums: vec![Color::default(); pixel_count],
+            weights: vec![0.0; pixel_count],
+            passes: 0,
+        }
+    }
+
+    // Number of passes that ran to completion
+    pub fn passes(&self) -> i64 {
+        self.passes
+    }
+
+    // Adds one sample to every pixel
+    pub fn render_pass(&mut self, world: &dyn Hittable, cam: &Camera, settings: &RenderSettings) {
+        self.run_pass(world, cam, settings, None);
+    }
+
+    // The image so far, scaled like render's output so it can go straight to write_color.
+    // Pixels that haven't been sampled yet are black.
+    pub fn image(&self, settings: &RenderSettings) -> Vec<Color> {
+        let samples_per_pixel = settings.samples_per_pixel as f64;
+        self.sums
+            .iter()
+            .zip(self.weights.iter())
+            .map(|(&sum, &weight)| {
+                if weight <= 0.0 {
+                    Color::default()
+                } else {
+                    sum * (samples_per_pixel / weight)
+                }
+            })
+            .collect()
+    }
+
+    // Samples every pixel once, top row first. With a deadline the clock is checked before each
+    // scanline and the pass is abandoned once it has passed, returning false. When
+    // settings.seed is set the generator is reseeded from it and the pass number, so pass n
+    // draws the same samples no matter how many passes came before it.
+    fn run_pass(
+        &mut self,
+        world: &dyn Hittable,
+        cam: &Camera,
+        settings: &RenderSettings,
+        deadline: Option<Instant>,
+    ) -> bool {
+        let image_height = settings.image_height();
+        let region = settings.region();
+
+        if let Some(seed) = settings.seed {
+            seed_random(seed.wrapping_add(self.passes as u64));
+        }
+
+        for row in region.y0..region.y1 {
+            if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                return false;
+            }
+
+            // Rows are stored top-down but sample_pixel counts up from the bottom
+            let draw_height = image_height - 1 - row;
+            for col in region.x0..region.x1 {
+                let index = ((row - region.y0) * region.width() + col - region.x0) as usize;
+                let (sum, weight) = sample_pixel(world, cam, settings, col, draw_height, 1, None);
+                self.sums[index] += sum;
+                self.weights[index] += weight;
+            }
+        }
+
+        self.passes += 1;
+        true
+    }
+}
+
+// Renders samples_per_pixel passes, handing the image to `on_pass` after each one along with
+// the number of passes done, e.g. to refresh a preview window. Returns the finished image.
+pub fn render_progressive<F>(
+    world: &dyn Hittable,
+    cam: &Camera,
+    settings: &RenderSettings,
+    mut on_pass: F,
+) -> Vec<Color>
+where
+    F: FnMut(i64, &[Color]),
+{
+    let mut progressive = ProgressiveRender::new(settings);
+
+    for _ in 0..settings.samples_per_pixel {
+        progressive.render_pass(world, cam, settings);
+        on_pass(progressive.passes(), &progressive.image(settings));
+    }
+
+    progressive.image(settings)
+}
+
+// Progressive render that stops after samples_per_pixel passes or once `budget` has elapsed.
+// The first pass always runs to completion so no pixel is left empty. Later passes can stop
+// partway down the image, those rows simply end up with one sample fewer. `on_pass` gets the
+// number of passes done after each complete one, e.g. to report progress.
+pub fn render_with_budget<F>(
+    world: &dyn Hittable,
+    cam: &Camera,
+    settings: &RenderSettings,
+    budget: Duration,
+    mut on_pass: F,
+) -> Vec<Color>
+where
+    F: FnMut(i64),
+{
+    let deadline = Instant::now() + budget;
+    let mut progressive = ProgressiveRender::new(settings);
+
+    progressive.render_pass(world, cam, settings);
+    on_pass(progressive.passes());
+    while progressive.passes() < settings.samples_per_pixel {
+        if !progressive.run_pass(world, cam, settings, Some(deadline)) {
+            break;
+        }
+        on_pass(progressive.passes());
+    }
+
+    progressive.image(settings)
+}
@@ -0,0 +1,22 @@
+use crate::vec3::{Point3, Vec3};
+
+// Ray stuff
+
+#[derive(Copy, Clone)]
+#[derive(Default)]
+pub struct Ray {
+    pub origin: Point3,
+    pub direction: Vec3,
+}
+
+impl Ray {
+    pub fn at(self, t: f64) -> Point3 {
+        self.origin + (self.direction * t)
+    }
+    pub fn new(origin: Point3, direction: Vec3) -> Ray {
+        Ray {
+            origin,
+            direction,
+        }
+    }
+}
@@ -0,0 +1,1411 @@
+use crate::camera::Camera;
+use crate::coverage::{refine_coverage, CoverageSettings};
+use crate::denoise::DenoiseSettings;
+use crate::environment::EnvironmentMap;
+use crate::error::Error;
+use crate::hittable::{HitRecord, Hittable, HittableList};
+use crate::material::offset_origin;
+use crate::preview::{render_edge_preview, EdgePreviewSettings};
+use crate::progressive::render_with_budget;
+use crate::ray::Ray;
+use crate::sky::PhysicalSky;
+use crate::spectral::{trace_spectral, SpectralSettings};
+use crate::supersample::{downsample, downsample_ids, upscaled_settings};
+use crate::tonemap::AutoExposure;
+use crate::utility::{clamp, random_float_range, seed_random, with_rng};
+use crate::vec3::{dot, Color, Vec3};
+use rand::Rng;
+use std::env;
+use std::ops::Range;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::{mpsc, Arc};
+use std::thread;
+use std::time::Duration;
+
+// This is basically the pixel output function
+pub fn write_color(append_string: &mut String, pixel_color: Color, samples_per_pixel: i64) {
+    write_color_with_gamma(
+        append_string,
+        pixel_color,
+        samples_per_pixel,
+        GammaMode::Gamma2,
+    );
+}
+
+// Same as write_color, with the transfer curve picked by `gamma`
+pub fn write_color_with_gamma(
+    append_string: &mut String,
+    pixel_color: Color,
+    samples_per_pixel: i64,
+    gamma: GammaMode,
+) {
+    let [r, g, b] = encode_color(pixel_color, samples_per_pixel, gamma);
+    let output = format!("{} {} {}\n", r, g, b);
+    append_string.push_str(&output);
+}
+
+// The 8-bit value write_color would print for each channel: average, apply the curve, clamp
+// and quantize
+pub fn encode_color(pixel_color: Color, samples_per_pixel: i64, gamma: GammaMode) -> [u8; 3] {
+    let scale = 1.0 / (samples_per_pixel as f64);
+    let channel = |value: f64| {
+        let encoded = gamma.encode(scale * value);
+        (256.0 * clamp(encoded, 0.0, 0.999)) as u8
+    };
+    [
+        channel(pixel_color.x),
+        channel(pixel_color.y),
+        channel(pixel_color.z),
+    ]
+}
+
+// Transfer curve applied to linear radiance before it is quantized to 8 bits
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum GammaMode {
+    // Square root, the book's "gamma 2"
+    Gamma2,
+    // The piecewise sRGB curve, with its linear toe near black
+    Srgb,
+    // No curve at all
+    Linear,
+    // value^(1/gamma), e.g. 2.2 or 2.4 to match a particular display. Custom(2.0) gives the
+    // same bytes as Gamma2.
+    Custom(f64),
+}
+
+impl GammaMode {
+    pub fn encode(self, value: f64) -> f64 {
+        match self {
+            GammaMode::Gamma2 => value.sqrt(),
+            GammaMode::Srgb => {
+                if value <= 0.0031308 {
+                    12.92 * value
+                } else {
+                    1.055 * value.powf(1.0 / 2.4) - 0.055
+                }
+            }
+            GammaMode::Linear => value,
+            GammaMode::Custom(gamma) => {
+                if gamma == 2.0 {
+                    value.sqrt()
+                } else {
+                    value.powf(1.0 / gamma)
+                }
+            }
+        }
+    }
+}
+
+// Parses "srgb", "linear" or a gamma exponent like "2.2"
+fn parse_gamma(value: &str) -> Result<GammaMode, Error> {
+    match value.trim().to_ascii_lowercase().as_str() {
+        "srgb" => Ok(GammaMode::Srgb),
+        "linear" => Ok(GammaMode::Linear),
+        other => other.parse().map(GammaMode::Custom).map_err(|_| {
+            Error::InvalidConfig(format!(
+                "gamma must be 'srgb', 'linear' or a number, got '{}'",
+                value
+            ))
+        }),
+    }
+}
+
+// Reconstruction filter, weights each sample by its offset from the pixel center
+
+#[derive(Copy, Clone)]
+pub enum PixelFilter {
+    // Every sample inside the pixel counts the same
+    Box,
+    // Weight falls off linearly, reaching zero `radius` pixels from the center
+    Tent { radius: f64 },
+    // Gaussian falloff with standard deviation `sigma`, cut off at `radius` pixels
+    Gaussian { radius: f64, sigma: f64 },
+}
+
+impl PixelFilter {
+    // Half-width of the square that samples are drawn from, in pixels. Must be above zero.
+    pub fn radius(&self) -> f64 {
+        match *self {
+            PixelFilter::Box => 0.5,
+            PixelFilter::Tent { radius } => radius,
+            PixelFilter::Gaussian { radius, .. } => radius,
+        }
+    }
+
+    pub fn weight(&self, dx: f64, dy: f64) -> f64 {
+        match *self {
+            PixelFilter::Box => 1.0,
+            PixelFilter::Tent { radius } => {
+                (1.0 - dx.abs() / radius).max(0.0) * (1.0 - dy.abs() / radius).max(0.0)
+            }
+            PixelFilter::Gaussian { radius, sigma } => {
+                // Subtracting the value at the radius makes the weight reach zero at the edge
+                let gaussian = |d: f64| {
+                    let edge = f64::exp(-(radius * radius) / (2.0 * sigma * sigma));
+                    (f64::exp(-(d * d) / (2.0 * sigma * sigma)) - edge).max(0.0)
+                };
+                gaussian(dx) * gaussian(dy)
+            }
+        }
+    }
+}
+
+// How sample_clamp brings an overly bright sample back down
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum ClampMode {
+    // Each channel is capped on its own. Cheap, but a bright saturated color drifts towards
+    // white as its strongest channels hit the limit.
+    PerChannel,
+    // The whole sample is scaled down until its luminance is at the limit, keeping the ratio
+    // between channels and so the hue
+    Luminance,
+}
+
+impl ClampMode {
+    pub fn apply(self, sample: Color, limit: f64) -> Color {
+        match self {
+            ClampMode::PerChannel => Color::new(
+                sample.x.min(limit),
+                sample.y.min(limit),
+                sample.z.min(limit),
+            ),
+            ClampMode::Luminance => {
+                let sample_luminance = luminance(sample);
+                if sample_luminance > limit {
+                    sample * (limit / sample_luminance)
+                } else {
+                    sample
+                }
+            }
+        }
+    }
+}
+
+// Which world axis points up. The sky gradient and the default camera vup follow it, so scenes
+// authored Z-up don't come out lying on their side.
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum UpAxis {
+    Y,
+    Z,
+}
+
+impl UpAxis {
+    pub fn vector(self) -> Vec3 {
+        match self {
+            UpAxis::Y => Vec3::new(0.0, 1.0, 0.0),
+            UpAxis::Z => Vec3::new(0.0, 0.0, 1.0),
+        }
+    }
+
+    // How far `v` points up along this axis
+    pub fn height(self, v: Vec3) -> f64 {
+        match self {
+            UpAxis::Y => v.y,
+            UpAxis::Z => v.z,
+        }
+    }
+}
+
+// Basis the normal AOV is written in
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum NormalSpace {
+    World,
+    // x to the right of the image, y up it and z back towards the viewer, see
+    // Camera::to_camera_space
+    Camera,
+}
+
+// What rays that leave the scene see when there is no environment map
+
+#[derive(Copy, Clone)]
+pub enum Background {
+    // White at the horizon to light blue overhead, the original look
+    Gradient,
+    // Daylight worked out from the sun's position and the haze, see PhysicalSky
+    Sky(PhysicalSky),
+    // The same color in every direction. Black leaves emissive materials as the only light.
+    Solid(Color),
+}
+
+// Rectangle of pixels to render, in full-frame pixel coordinates with the origin at the top
+// left. x1 and y1 are exclusive. The camera still maps the whole frame, so a crop renders
+// exactly the pixels it would have covered in the full image.
+
+#[derive(Copy, Clone, PartialEq)]
+pub struct Crop {
+    pub x0: i64,
+    pub y0: i64,
+    pub x1: i64,
+    pub y1: i64,
+}
+
+impl Crop {
+    pub fn new(x0: i64, y0: i64, x1: i64, y1: i64) -> Crop {
+        Crop { x0, y0, x1, y1 }
+    }
+
+    pub fn width(&self) -> i64 {
+        (self.x1 - self.x0).max(0)
+    }
+
+    pub fn height(&self) -> i64 {
+        (self.y1 - self.y0).max(0)
+    }
+
+    // Pulls every edge inside an image_width x image_height frame
+    pub fn clamped(self, image_width: i64, image_height: i64) -> Crop {
+        Crop {
+            x0: self.x0.clamp(0, image_width),
+            y0: self.y0.clamp(0, image_height),
+            x1: self.x1.clamp(0, image_width),
+            y1: self.y1.clamp(0, image_height),
+        }
+    }
+}
+
+// Render settings
+
+#[derive(Clone)]
+pub struct RenderSettings {
+    // Only used to derive the image height from the width, see image_height
+    pub aspect_ratio: f64,
+    pub image_width: i64,
+    // Asks for an exact height, in which case aspect_ratio is ignored entirely
+    pub fixed_height: Option<i64>,
+    pub samples_per_pixel: i64,
+    // Hard cap on the number of bounces a path may take
+    pub max_depth: i64,
+    // A path is terminated once every channel of its throughput drops below this, since further
+    // bounces would contribute next to nothing. 0.0 disables the cutoff.
+    pub min_throughput: f64,
+    pub filter: PixelFilter,
+    // Caps each channel of every individual sample before it is filtered, which keeps rare
+    // very bright paths from turning into fireflies. None leaves samples untouched.
+    pub sample_clamp: Option<f64>,
+    pub clamp_mode: ClampMode,
+    pub up_axis: UpAxis,
+    // When set, the beauty pass is run through the joint bilateral denoiser before output
+    pub denoise: Option<DenoiseSettings>,
+    // Name of the scene to render, see scene_by_name, or the path of a .json scene file (see
+    // Scene) or of an .obj mesh, which main shades gray
+    pub scene: String,
+    // Where main writes the image, the extension picks the format (see FileSink)
+    pub output: String,
+    // When set, render gives a fast preview that only super-samples edge pixels, in place of
+    // taking samples_per_pixel samples everywhere
+    pub edge_preview: Option<EdgePreviewSettings>,
+    // Lights the scene from an image instead of the sky gradient
+    pub environment: Option<Arc<EnvironmentMap>>,
+    // Sky used when there is no environment map
+    pub background: Background,
+    // Emitters to aim bounces at, which cuts the noise from small bright lights a lot. They
+    // stay in the world as well, this list is only used for sampling and everything in it has
+    // to implement Hittable::pdf_value and random (spheres do). None or an empty list traces
+    // plain paths.
+    pub lights: Option<Arc<HittableList>>,
+    // Rescales the finished image towards a middle-gray average before it is written out
+    pub auto_exposure: Option<AutoExposure>,
+    // When set, render samples the image in passes and returns whatever it has once this much
+    // wall-clock time is used up, instead of always taking samples_per_pixel samples
+    pub time_budget: Option<Duration>,
+    // Seeds the random generator so the same settings give the same image. Progressive renders
+    // reseed every pass. None seeds from the OS.
+    pub seed: Option<u64>,
+    // Renders only this part of the frame, and the output image is just the crop
+    pub crop: Option<Crop>,
+    // Renders at this many times the resolution in each direction and box filters back down,
+    // on top of samples_per_pixel. 1 turns supersampling off.
+    pub ssaa: i64,
+    // When set, pixels around strong disagreements between neighbours get extra samples, so
+    // thin objects don't drop out
+    pub coverage: Option<CoverageSettings>,
+    // Transfer curve used when writing 8-bit output
+    pub gamma: GammaMode,
+    // When set, samples trace single wavelengths instead of RGB so glass can disperse light.
+    // The specular AOV stays black in this mode.
+    pub spectral: Option<SpectralSettings>,
+    // validate rejects images whose pixel buffer would need more memory than this, so a typo
+    // in the width fails with a message instead of the allocation taking the machine down
+    pub max_image_bytes: u64,
+    // Color for rays that leave the scene below the horizon, instead of letting the sky carry
+    // on down. Ignored when there is an environment map.
+    pub ground_color: Option<Color>,
+    // Basis the normal AOV is written in. Denoising and edge previews only compare normals
+    // with each other, so they work the same in either.
+    pub normal_space: NormalSpace,
+    // Rows are rendered on this many threads at once, 1 renders on the calling thread only.
+    // Defaults to the number of CPUs. Progressive and time-budgeted renders ignore it and
+    // always run on the calling thread.
+    pub threads: usize,
+}
+
+impl Default for RenderSettings {
+    fn default() -> RenderSettings {
+        RenderSettings {
+            aspect_ratio: 16.0 / 9.0,
+            image_width: 2560,
+            fixed_height: None,
+            samples_per_pixel: 500,
+            max_depth: 50,
+            min_throughput: 0.0,
+            filter: PixelFilter::Box,
+            sample_clamp: None,
+            clamp_mode: ClampMode::PerChannel,
+            up_axis: UpAxis::Y,
+            denoise: None,
+            scene: String::from("random"),
+            output: String::from(
+                "/home/edthelegendary/Pictures/Wallpapers/2kRayTracerWallpaper.ppm",
+            ),
+            edge_preview: None,
+            environment: None,
+            background: Background::Gradient,
+            lights: None,
+            auto_exposure: None,
+            time_budget: None,
+            seed: None,
+            crop: None,
+            ssaa: 1,
+            coverage: None,
+            gamma: GammaMode::Gamma2,
+            spectral: None,
+            max_image_bytes: 8 << 30,
+            ground_color: None,
+            normal_space: NormalSpace::World,
+            threads: thread::available_parallelism().map_or(1, |count| count.get()),
+        }
+    }
+}
+
+impl RenderSettings {
+    // Settings are resolved in order of precedence: command-line arguments first, then the
+    // RAYTRACER_WIDTH, RAYTRACER_HEIGHT, RAYTRACER_SAMPLES, RAYTRACER_THREADS, RAYTRACER_SCENE,
+    // RAYTRACER_OUTPUT, RAYTRACER_CROP ("x0 y0 x1 y1") and RAYTRACER_GAMMA environment
+    // variables, and finally the defaults above. This applies the last two layers, apply_args
+    // puts the arguments on top of the result. Only values that don't parse fail here, the
+    // combined settings are checked once at the end by apply_args (or call validate when there
+    // are no arguments), so an argument can still override a bad environment variable.
+    pub fn from_env() -> Result<RenderSettings, Error> {
+        let mut settings = RenderSettings::default();
+
+        if let Some(width) = env_number("RAYTRACER_WIDTH")? {
+            settings.image_width = width;
+        }
+        if let Some(height) = env_number("RAYTRACER_HEIGHT")? {
+            settings.fixed_height = Some(height);
+        }
+        if let Some(samples) = env_number("RAYTRACER_SAMPLES")? {
+            settings.samples_per_pixel = samples;
+        }
+        if let Some(threads) = env_number("RAYTRACER_THREADS")? {
+            settings.threads = threads;
+        }
+        if let Ok(scene) = env::var("RAYTRACER_SCENE") {
+            settings.scene = scene;
+        }
+        if let Ok(output) = env::var("RAYTRACER_OUTPUT") {
+            settings.output = output;
+        }
+        if let Ok(crop) = env::var("RAYTRACER_CROP") {
+            settings.crop = Some(parse_crop(&crop)?);
+        }
+        if let Ok(gamma) = env::var("RAYTRACER_GAMMA") {
+            settings.gamma = parse_gamma(&gamma)?;
+        }
+
+        Ok(settings)
+    }
+
+    // Applies command-line arguments, each given as "--name value" or "--name=value":
+    //   --output <path>  --width <pixels>  --samples <count>  --max-depth <bounces>
+    //   --threads <count>  --crop <x0> <y0> <x1> <y1>
+    // The crop can also be given as one value, "--crop=x0,y0,x1,y1". The height still follows
+    // from the width and aspect ratio. Pass the arguments without the
+    // program name, e.g. env::args().skip(1). Validates the settings once all arguments are in.
+    pub fn apply_args<I: IntoIterator<Item = String>>(&mut self, args: I) -> Result<(), Error> {
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            let (name, inline_value) = match arg.split_once('=') {
+                Some((name, value)) => (String::from(name), Some(String::from(value))),
+                None => (arg, None),
+            };
+            let inline = inline_value.is_some();
+            if !matches!(
+                name.as_str(),
+                "--output" | "--width" | "--samples" | "--max-depth" | "--threads" | "--crop"
+            ) {
+                return Err(Error::InvalidConfig(format!(
+                    "Unknown argument '{}', expected --output, --width, --samples, --max-depth, \
+                     --threads or --crop",
+                    name
+                )));
+            }
+            let value = match inline_value.or_else(|| args.next()) {
+                Some(value) => value,
+                None => {
+                    return Err(Error::InvalidConfig(format!("{} needs a value", name)));
+                }
+            };
+
+            match name.as_str() {
+                "--output" => self.output = value,
+                "--width" => self.image_width = arg_number(&name, &value)?,
+                "--samples" => self.samples_per_pixel = arg_number(&name, &value)?,
+                "--threads" => self.threads = arg_number(&name, &value)?,
+                "--crop" => {
+                    // Given as four separate arguments unless they all came in the first one
+                    let mut crop = value;
+                    if !inline && crop.trim().parse::<i64>().is_ok() {
+                        for _ in 0..3 {
+                            let next = args.next().ok_or_else(|| {
+                                Error::InvalidConfig(String::from(
+                                    "--crop needs four values, x0 y0 x1 y1",
+                                ))
+                            })?;
+                            crop.push(' ');
+                            crop.push_str(&next);
+                        }
+                    }
+                    self.crop = Some(parse_crop(&crop)?);
+                }
+                _ => self.max_depth = arg_number(&name, &value)?,
+            }
+        }
+
+        self.validate()
+    }
+
+    // Catches settings that would silently produce a garbage image, like zero samples per
+    // pixel (write_color divides by it), a zero filter radius or an empty image
+    pub fn validate(&self) -> Result<(), Error> {
+        if self.samples_per_pixel < 1 {
+            return Err(Error::InvalidConfig(format!(
+                "samples_per_pixel must be at least 1, got {}",
+                self.samples_per_pixel
+            )));
+        }
+        if self.threads < 1 {
+            return Err(Error::InvalidConfig(String::from(
+                "threads must be at least 1, got 0",
+            )));
+        }
+        if self.ssaa < 1 {
+            return Err(Error::InvalidConfig(format!(
+                "ssaa must be at least 1, got {}",
+                self.ssaa
+            )));
+        }
+        match self.filter {
+            PixelFilter::Box => {}
+            PixelFilter::Tent { radius } => check_positive("filter radius", radius)?,
+            PixelFilter::Gaussian { radius, sigma } => {
+                check_positive("filter radius", radius)?;
+                check_positive("filter sigma", sigma)?;
+            }
+        }
+        if let Some(denoise) = &self.denoise {
+            if denoise.radius < 0 {
+                return Err(Error::InvalidConfig(format!(
+                    "denoise radius can't be negative, got {}",
+                    denoise.radius
+                )));
+            }
+            check_positive("denoise sigma_spatial", denoise.sigma_spatial)?;
+            check_positive("denoise sigma_color", denoise.sigma_color)?;
+            check_positive("denoise sigma_albedo", denoise.sigma_albedo)?;
+            check_positive("denoise sigma_normal", denoise.sigma_normal)?;
+        }
+        if let Some(preview) = &self.edge_preview {
+            if preview.edge_samples < 1 {
+                return Err(Error::InvalidConfig(format!(
+                    "edge_samples must be at least 1, got {}",
+                    preview.edge_samples
+                )));
+            }
+        }
+        if let Some(coverage) = &self.coverage {
+            if coverage.extra_samples < 1 {
+                return Err(Error::InvalidConfig(format!(
+                    "coverage extra_samples must be at least 1, got {}",
+                    coverage.extra_samples
+                )));
+            }
+        }
+        if let Some(spectral) = &self.spectral {
+            if spectral.wavelength_samples < 1 {
+                return Err(Error::InvalidConfig(format!(
+                    "wavelength_samples must be at least 1, got {}",
+                    spectral.wavelength_samples
+                )));
+            }
+        }
+        if let GammaMode::Custom(gamma) = self.gamma {
+            if !(gamma > 0.0 && gamma.is_finite()) {
+                return Err(Error::InvalidConfig(format!(
+                    "gamma must be a positive number, got {}",
+                    gamma
+                )));
+            }
+        }
+        if self.region().width() < 1 || self.region().height() < 1 {
+            return Err(Error::InvalidConfig(format!(
+                "image must be at least 1x1 pixels, got {}x{}",
+                self.image_width,
+                self.image_height()
+            )));
+        }
+        let needed = self.image_bytes();
+        if needed > self.max_image_bytes as u128 {
+            let gigabytes = |bytes: u128| bytes as f64 / (1u64 << 30) as f64;
+            return Err(Error::InvalidConfig(format!(
+                "a {}x{} image needs {:.1} GB for its pixels, over the {:.1} GB limit",
+                self.image_width,
+                self.image_height(),
+                gigabytes(needed),
+                gigabytes(self.max_image_bytes as u128)
+            )));
+        }
+        Ok(())
+    }
+
+    // Size of the largest pixel buffer a render allocates, the supersampled one when ssaa is on
+    pub fn image_bytes(&self) -> u128 {
+        let region = self.region();
+        let ssaa = self.ssaa.max(1) as u128;
+        let pixels = region.width().max(0) as u128 * region.height().max(0) as u128;
+        pixels * ssaa * ssaa * std::mem::size_of::<Color>() as u128
+    }
+
+    // Pulls the crop inside the frame. Returns the crop as it was if it had to be moved, so the
+    // caller can warn about it.
+    pub fn clamp_crop(&mut self) -> Option<Crop> {
+        let crop = self.crop?;
+        let clamped = crop.clamped(self.image_width, self.image_height());
+        self.crop = Some(clamped);
+        (clamped != crop).then_some(crop)
+    }
+
+    // The part of the frame that actually gets rendered, which is also the size of every
+    // buffer render hands back
+    pub fn region(&self) -> Crop {
+        let full = Crop::new(0, 0, self.image_width, self.image_height());
+        match self.crop {
+            Some(crop) => crop.clamped(self.image_width, self.image_height()),
+            None => full,
+        }
+    }
+
+    // fixed_height when set, otherwise image_width / aspect_ratio rounded down
+    pub fn image_height(&self) -> i64 {
+        match self.fixed_height {
+            Some(height) => height,
+            None => (self.image_width as f64 / self.aspect_ratio) as i64,
+        }
+    }
+
+    // Aspect ratio of the pixels actually rendered, which is what the camera should be built
+    // with. Can differ slightly from aspect_ratio since the height is rounded.
+    pub fn pixel_aspect_ratio(&self) -> f64 {
+        self.image_width as f64 / self.image_height() as f64
+    }
+}
+
+// Relative luminance of a linear color (Rec. 709 weights)
+pub fn luminance(color: Color) -> f64 {
+    0.2126 * color.x + 0.7152 * color.y + 0.0722 * color.z
+}
+
+// For settings that divide by their value, where zero, negative or NaN would give NaN pixels
+fn check_positive(name: &str, value: f64) -> Result<(), Error> {
+    if value > 0.0 && value.is_finite() {
+        Ok(())
+    } else {
+        Err(Error::InvalidConfig(format!(
+            "{} must be a positive number, got {}",
+            name, value
+        )))
+    }
+}
+
+// Reads a numeric environment variable, treating an unset variable as absent
+fn env_number<T: FromStr>(name: &str) -> Result<Option<T>, Error> {
+    match env::var(name) {
+        Ok(value) => match value.trim().parse() {
+            Ok(number) => Ok(Some(number)),
+            Err(_) => Err(Error::InvalidConfig(format!(
+                "{} must be a number, got '{}'",
+                name, value
+            ))),
+        },
+        Err(_) => Ok(None),
+    }
+}
+
+fn arg_number<T: FromStr>(name: &str, value: &str) -> Result<T, Error> {
+    value
+        .trim()
+        .parse()
+        .map_err(|_| Error::InvalidConfig(format!("{} must be a number, got '{}'", name, value)))
+}
+
+// Parses "x0 y0 x1 y1", separated by spaces or commas
+fn parse_crop(value: &str) -> Result<Crop, Error> {
+    let invalid = || {
+        Error::InvalidConfig(format!(
+            "crop must be four integers 'x0 y0 x1 y1', got '{}'",
+            value
+        ))
+    };
+    let numbers: Vec<i64> = value
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|part| !part.is_empty())
+        .map(|part| part.parse())
+        .collect::<Result<_, _>>()
+        .map_err(|_| invalid())?;
+
+    match numbers[..] {
+        [x0, y0, x1, y1] => Ok(Crop::new(x0, y0, x1, y1)),
+        _ => Err(invalid()),
+    }
+}
+
+// Closest hit distance a ray accepts. Scattered rays already start nudged off their surface
+// (see offset_origin), so this only has to absorb what's left of the rounding error and can be
+// much smaller than the thickness of a thin glass shell.
+pub(crate) const T_MIN: f64 = 1e-5;
+
+// Ray color thing
+pub fn ray_color(r: Ray, world: &dyn Hittable, settings: &RenderSettings) -> Color {
+    trace(r, world, settings).0
+}
+
+// What a ray that leaves the scene sees: the environment map if there is one, otherwise
+// settings.background, with settings.ground_color below the horizon when it is set
+pub(crate) fn background(ray: Ray, settings: &RenderSettings) -> Color {
+    if let Some(environment) = &settings.environment {
+        return environment.value(ray.direction);
+    }
+    let unit_direction = Vec3::unit_vector(ray.direction);
+    let height = settings.up_axis.height(unit_direction);
+    let (sky, horizon) = match &settings.background {
+        Background::Gradient => {
+            let t = (height + 1.0) * 0.5;
+            let sky = (Vec3::new(1.0, 1.0, 1.0) * (1.0 - t)) + (Vec3::new(0.5, 0.7, 1.0) * t);
+            (sky, Vec3::new(0.75, 0.85, 1.0))
+        }
+        // Below the horizon the sky already holds its horizon value
+        Background::Sky(sky) => {
+            let value = sky.value(unit_direction, settings.up_axis.vector());
+            (value, value)
+        }
+        Background::Solid(color) => (*color, *color),
+    };
+
+    match settings.ground_color {
+        Some(ground) if height < 0.0 => {
+            // Fade from the horizon into the ground over the first few degrees, like haze
+            let fade = (-height / 0.1).min(1.0);
+            horizon * (1.0 - fade) + ground * fade
+        }
+        _ => sky,
+    }
+}
+
+// Follows one path and returns its radiance, together with the part of it that arrived by way
+// of a specular reflection (off a metal, or the reflective lobe of glass) at the first surface.
+// Light emitted by the first surface itself is never counted as specular.
+fn trace(r: Ray, world: &dyn Hittable, settings: &RenderSettings) -> (Color, Color) {
+    let mut ray = r;
+    let mut throughput = Color::new(1.0, 1.0, 1.0);
+    let mut radiance = Color::default();
+    // Radiance emitted at the first hit, and whether the first bounce was specular
+    let mut first_hit: Option<(Color, bool)> = None;
+
+    for _ in 0..settings.max_depth {
+        let rec = match world.hit2(ray, T_MIN, f64::INFINITY) {
+            Some(rec) => rec,
+            None => {
+                radiance += throughput * background(ray, settings);
+                break;
+            }
+        };
+
+        radiance += throughput * rec.mat_ptr.emitted(&rec);
+
+        let mut scattered = Ray::default();
+        let mut attenuation = Color::default();
+
+        let scatters = with_rng(|rng| {
+            rec.mat_ptr
+                .scatter(ray, rec.clone(), &mut attenuation, &mut scattered, rng)
+        });
+        if first_hit.is_none() {
+            let reflected = dot(scattered.direction, rec.normal) > 0.0;
+            first_hit = Some((radiance, rec.mat_ptr.is_specular() && reflected));
+        }
+        if !scatters {
+            break;
+        }
+        if let Some(weight) = sample_lights(ray, &rec, &mut scattered, settings) {
+            attenuation = weight;
+        }
+
+        throughput = throughput * attenuation;
+        if throughput.x < settings.min_throughput
+            && throughput.y < settings.min_throughput
+            && throughput.z < settings.min_throughput
+        {
+            break;
+        }
+        ray = scattered;
+    }
+
+    let specular = match first_hit {
+        Some((emitted, true)) => radiance - emitted,
+        _ => Color::default(),
+    };
+    (radiance, specular)
+}
+
+// With settings.lights set, half of the bounces off materials that have a bsdf are redirected
+// towards a random point on one of the lights. Either way the bounce is weighted by the
+// density of both strategies together, which keeps the estimate unbiased. Returns the weight
+// for the bounce left in `scattered`, or None to keep the material's own sample and weight.
+pub(crate) fn sample_lights(
+    ray: Ray,
+    rec: &HitRecord,
+    scattered: &mut Ray,
+    settings: &RenderSettings,
+) -> Option<Color> {
+    let lights = settings
+        .lights
+        .as_ref()
+        .filter(|lights| !lights.is_empty())?;
+    rec.mat_ptr.bsdf(ray, rec, scattered.direction)?;
+
+    let material_direction = scattered.direction;
+    let direction = with_rng(|rng| {
+        if rng.gen::<bool>() {
+            lights.random(rec.p, rng)
+        } else {
+            material_direction
+        }
+    });
+    let (value, material_pdf) = rec.mat_ptr.bsdf(ray, rec, direction)?;
+    let pdf = 0.5 * lights.pdf_value(rec.p, direction) + 0.5 * material_pdf;
+
+    *scattered = Ray::new(offset_origin(rec.p, rec.normal, direction), direction);
+    if pdf <= 0.0 {
+        return Some(Color::default());
+    }
+    Some(value / pdf)
+}
+
+// Feature buffers taken from the primary hit of every sample, averaged per pixel and stored in
+// the same order as the beauty pass. Rays that miss leave albedo and normal at zero. IDs can't
+// be averaged, so each pixel keeps the object ID its first sample hit. The specular pass holds
+// the share of the beauty pass that was reflected off the first surface, filtered and scaled
+// the same way, so it goes through write_color and can be subtracted or boosted in post.
+#[derive(Clone, Default)]
+pub struct Aovs {
+    pub albedo: Vec<Color>,
+    pub normal: Vec<Vec3>,
+    pub object_id: Vec<u32>,
+    pub specular: Vec<Color>,
+}
+
+// Turns an object ID into a color for a mask image, hashing it so neighbouring IDs still come
+// out clearly different. ID 0 stays black.
+pub fn id_color(id: u32) -> Color {
+    if id == 0 {
+        return Color::default();
+    }
+
+    // Finalizer from MurmurHash3
+    let mut hash = id;
+    hash ^= hash >> 16;
+    hash = hash.wrapping_mul(0x85eb_ca6b);
+    hash ^= hash >> 13;
+    hash = hash.wrapping_mul(0xc2b2_ae35);
+    hash ^= hash >> 16;
+
+    let channel = |shift: u32| ((hash >> shift) & 0xff) as f64 / 255.0;
+    Color::new(channel(0), channel(8), channel(16))
+}
+
+// Renders the whole image (or just the crop), returning one entry per pixel from the top row
+// down. Each entry is a sum over samples_per_pixel samples (or scaled to look like one), ready
+// for write_color.
+pub fn render(world: &dyn Hittable, cam: &Camera, settings: &RenderSettings) -> Vec<Color> {
+    if settings.ssaa > 1 {
+        let pixels = render(world, cam, &upscaled_settings(settings));
+        return downsample(&pixels, settings);
+    }
+    if let Some(preview) = &settings.edge_preview {
+        return render_edge_preview(world, cam, settings, preview);
+    }
+    if let Some(budget) = settings.time_budget {
+        return render_with_budget(world, cam, settings, budget, |_| {});
+    }
+    render_image(world, cam, settings, None)
+}
+
+// Merges renders of the same frame taken independently, e.g. with different seeds on several
+// machines, each given with the samples per pixel it was rendered with. render's pixels are
+// already sums over their samples, so the merged pixels are just their totals and count as
+// the total number of samples: ten 50-sample runs give a 500-sample image, to be written with
+// samples_per_pixel set to the returned count. Fails if the buffers differ in size.
+pub fn accumulate(buffers: &[(Vec<Color>, u64)]) -> Result<(Vec<Color>, u64), Error> {
+    let pixel_count = match buffers.first() {
+        Some((pixels, _)) => pixels.len(),
+        None => return Ok((Vec::new(), 0)),
+    };
+
+    let mut total = vec![Color::default(); pixel_count];
+    let mut samples = 0;
+    for (index, (pixels, count)) in buffers.iter().enumerate() {
+        if pixels.len() != pixel_count {
+            return Err(Error::InvalidConfig(format!(
+                "buffer {} has {} pixels, expected {} like the first",
+                index,
+                pixels.len(),
+                pixel_count
+            )));
+        }
+        for (sum, &pixel) in total.iter_mut().zip(pixels) {
+            *sum += pixel;
+        }
+        samples += count;
+    }
+
+    Ok((total, samples))
+}
+
+// Same as render, but also gathers the albedo and normal AOVs
+pub fn render_with_aovs(
+    world: &dyn Hittable,
+    cam: &Camera,
+    settings: &RenderSettings,
+) -> (Vec<Color>, Aovs) {
+    if settings.ssaa > 1 {
+        let (pixels, aovs) = render_with_aovs(world, cam, &upscaled_settings(settings));
+        let aovs = Aovs {
+            albedo: downsample(&aovs.albedo, settings),
+            normal: downsample(&aovs.normal, settings),
+            object_id: downsample_ids(&aovs.object_id, settings),
+            specular: downsample(&aovs.specular, settings),
+        };
+        return (downsample(&pixels, settings), aovs);
+    }
+
+    let mut aovs = Aovs::default();
+    let pixels = render_image(world, cam, settings, Some(&mut aovs));
+    (pixels, aovs)
+}
+
+// Single-precision pixel, half the size of a Color. Sums of a few thousand samples keep about
+// seven significant digits in an f32, which is far more than 8-bit output can show.
+#[derive(Copy, Clone, Default)]
+pub struct CompactColor {
+    pub r: f32,
+    pub g: f32,
+    pub b: f32,
+}
+
+impl From<Color> for CompactColor {
+    fn from(color: Color) -> CompactColor {
+        CompactColor {
+            r: color.x as f32,
+            g: color.y as f32,
+            b: color.z as f32,
+        }
+    }
+}
+
+impl From<CompactColor> for Color {
+    fn from(color: CompactColor) -> Color {
+        Color::new(color.r as f64, color.g as f64, color.b as f64)
+    }
+}
+
+// Like render, but stores the image as CompactColor to halve the memory of very large frames.
+// Each pixel's samples are still summed in f64 and only the finished sum is rounded to f32.
+// Takes the plain path through the renderer: crop and seed apply, while the edge preview, time
+// budget, SSAA and coverage pass (which all need a full precision buffer) are ignored.
+pub fn render_compact(
+    world: &dyn Hittable,
+    cam: &Camera,
+    settings: &RenderSettings,
+) -> Vec<CompactColor> {
+    let region = settings.region();
+    let mut pixels = Vec::with_capacity((region.width() * region.height()) as usize);
+
+    // Only a band of rows is held in full precision at a time
+    let band = (settings.threads.max(1) * 4) as i64;
+    let mut row = region.y0;
+    while row < region.y1 {
+        let rows = row..(row + band).min(region.y1);
+        let sums = render_rows(world, cam, settings, rows.clone(), None);
+        pixels.extend(sums.into_iter().map(CompactColor::from));
+        row = rows.end;
+    }
+
+    pixels
+}
+
+pub(crate) fn render_image(
+    world: &dyn Hittable,
+    cam: &Camera,
+    settings: &RenderSettings,
+    aovs: Option<&mut Aovs>,
+) -> Vec<Color> {
+    let region = settings.region();
+    let mut pixels = render_rows(world, cam, settings, region.y0..region.y1, aovs);
+
+    if let Some(coverage) = &settings.coverage {
+        refine_coverage(world, cam, settings, &mut pixels, coverage);
+    }
+
+    pixels
+}
+
+// Renders the region's part of full-frame rows `rows` (counted from the top), returned in
+// order. The rows are shared out between settings.threads threads as they become free. With a
+// seed every row reseeds the generator from it and the row number, so a seeded image comes out
+// the same whatever the thread count and whichever thread takes which row.
+pub(crate) fn render_rows(
+    world: &dyn Hittable,
+    cam: &Camera,
+    settings: &RenderSettings,
+    rows: Range<i64>,
+    mut aovs: Option<&mut Aovs>,
+) -> Vec<Color> {
+    let image_height = settings.image_height();
+    let region = settings.region();
+    let want_aovs = aovs.is_some();
+
+    let render_row = |row: i64| {
+        seed_row(settings, 0, row);
+
+        let draw_height = image_height - 1 - row;
+        let mut row_aovs = Aovs::default();
+        let pixels: Vec<Color> = (region.x0..region.x1)
+            .map(|draw_width| {
+                let aovs = if want_aovs { Some(&mut row_aovs) } else { None };
+                render_pixel(world, cam, settings, draw_width, draw_height, aovs)
+            })
+            .collect();
+        (row, pixels, row_aovs)
+    };
+
+    let row_count = (rows.end - rows.start).max(0) as usize;
+    let threads = settings.threads.clamp(1, row_count.max(1));
+    // Progress is only reported from the calling thread, as finished rows come back to it
+    let report = |done: usize| eprintln!("\rScanlines remaining: {} ", row_count - done);
+
+    let mut finished = Vec::with_capacity(row_count);
+    if threads == 1 {
+        for row in rows {
+            finished.push(render_row(row));
+            report(finished.len());
+        }
+    } else {
+        let next_row = AtomicI64::new(rows.start);
+        let end = rows.end;
+        let (sender, receiver) = mpsc::channel();
+        thread::scope(|scope| {
+            let workers: Vec<_> = (0..threads)
+                .map(|_| {
+                    let (next_row, render_row, sender) = (&next_row, &render_row, sender.clone());
+                    scope.spawn(move || loop {
+                        let row = next_row.fetch_add(1, Ordering::Relaxed);
+                        if row >= end || sender.send(render_row(row)).is_err() {
+                            return;
+                        }
+                    })
+                })
+                .collect();
+
+            // The receiver runs dry once every worker has dropped its sender
+            drop(sender);
+            for done in receiver {
+                finished.push(done);
+                report(finished.len());
+            }
+            for worker in workers {
+                worker
+                    .join()
+                    .unwrap_or_else(|panic| std::panic::resume_unwind(panic));
+            }
+        });
+        finished.sort_by_key(|(row, _, _)| *row);
+    }
+
+    let mut pixels = Vec::with_capacity(row_count * region.width() as usize);
+    for (_, row_pixels, row_aovs) in finished.drain(..) {
+        pixels.extend(row_pixels);
+        if let Some(aovs) = aovs.as_deref_mut() {
+            aovs.albedo.extend(row_aovs.albedo);
+            aovs.normal.extend(row_aovs.normal);
+            aovs.object_id.extend(row_aovs.object_id);
+            aovs.specular.extend(row_aovs.specular);
+        }
+    }
+    pixels
+}
+
+// Generator seed for one row of a seeded render, spread out so neighbouring rows don't get
+// related streams
+fn row_seed(seed: u64, row: i64) -> u64 {
+    seed ^ (row as u64)
+        .wrapping_add(1)
+        .wrapping_mul(0x9e37_79b9_7f4a_7c15)
+}
+
+// Reseeds the generator before full-frame row `row` of a seeded render, and does nothing
+// without a seed. Passes that revisit rows after the main one (pass 0) give their own `pass`
+// number, so their samples are reproducible on any thread without repeating the main pass's.
+pub(crate) fn seed_row(settings: &RenderSettings, pass: u64, row: i64) {
+    if let Some(seed) = settings.seed {
+        let pass_seed = seed.wrapping_add(pass.wrapping_mul(0xd1b5_4a32_d192_ed03));
+        seed_random(row_seed(pass_seed, row));
+    }
+}
+
+pub(crate) fn render_pixel(
+    world: &dyn Hittable,
+    cam: &Camera,
+    settings: &RenderSettings,
+    draw_width: i64,
+    draw_height: i64,
+    aovs: Option<&mut Aovs>,
+) -> Color {
+    let samples = settings.samples_per_pixel;
+    let (pixel_color, weight_sum) =
+        sample_pixel(world, cam, settings, draw_width, draw_height, samples, aovs);
+
+    if weight_sum <= 0.0 {
+        return Color::default();
+    }
+
+    // Rescale so that dividing by samples_per_pixel gives the filtered average
+    pixel_color * (samples as f64 / weight_sum)
+}
+
+// Takes `samples` filtered samples of one pixel and returns the weighted color sum together
+// with the sum of the weights, so callers can keep accumulating before normalizing
+pub(crate) fn sample_pixel(
+    world: &dyn Hittable,
+    cam: &Camera,
+    settings: &RenderSettings,
+    draw_width: i64,
+    draw_height: i64,
+    samples: i64,
+    aovs: Option<&mut Aovs>,
+) -> (Color, f64) {
+    let image_width = settings.image_width;
+    let image_height = settings.image_height();
+    let radius = settings.filter.radius();
+
+    let mut pixel_color = Color::default();
+    let mut weight_sum = 0.0;
+    let mut albedo_sum = Color::default();
+    let mut normal_sum = Vec3::default();
+    let mut object_id = None;
+    let mut specular_sum = Color::default();
+
+    for _ in 0..samples {
+        let dx = random_float_range(-radius, radius);
+        let dy = random_float_range(-radius, radius);
+        let u = (draw_width as f64 + 0.5 + dx) / (image_width as f64 - 1.0);
+        let v = (draw_height as f64 + 0.5 + dy) / (image_height as f64 - 1.0);
+        let r = cam.get_ray(u, v);
+
+        if aovs.is_some() {
+            let (albedo, normal, id) = primary_features(r, world);
+            albedo_sum += albedo;
+            normal_sum += normal;
+            object_id.get_or_insert(id);
+        }
+
+        let (mut sample, mut specular) = match &settings.spectral {
+            Some(spectral) => (
+                trace_spectral(r, world, settings, spectral),
+                Color::default(),
+            ),
+            None => trace(r, world, settings),
+        };
+        if let Some(limit) = settings.sample_clamp {
+            sample = settings.clamp_mode.apply(sample, limit);
+            specular = settings.clamp_mode.apply(specular, limit);
+        }
+
+        let weight = settings.filter.weight(dx, dy);
+        pixel_color += sample * weight;
+        specular_sum += specular * weight;
+        weight_sum += weight;
+    }
+
+    if let Some(aovs) = aovs {
+        let scale = 1.0 / samples as f64;
+        aovs.albedo.push(albedo_sum * scale);
+        let normal = match settings.normal_space {
+            NormalSpace::World => normal_sum,
+            NormalSpace::Camera => cam.to_camera_space(normal_sum),
+        };
+        aovs.normal.push(normal * scale);
+        aovs.object_id.push(object_id.unwrap_or(0));
+        if weight_sum > 0.0 {
+            aovs.specular
+                .push(specular_sum * (samples as f64 / weight_sum));
+        } else {
+            aovs.specular.push(Color::default());
+        }
+    }
+
+    (pixel_color, weight_sum)
+}
+
+// Albedo, shading normal and object ID of the first surface `r` hits. The albedo is whatever
+// attenuation the material reports for a single scatter.
+fn primary_features(r: Ray, world: &dyn Hittable) -> (Color, Vec3, u32) {
+    match world.hit2(r, T_MIN, f64::INFINITY) {
+        Some(rec) => {
+            let mut scattered = Ray::default();
+            let mut attenuation = Color::default();
+            with_rng(|rng| {
+                rec.mat_ptr
+                    .scatter(r, rec.clone(), &mut attenuation, &mut scattered, rng)
+            });
+            (attenuation, rec.normal, rec.object_id)
+        }
+        None => (Color::default(), Vec3::default(), 0),
+    }
+}
+
+// First surface under image coordinates (u, v), each in [0, 1] with v = 0 at the bottom like
+// Camera::get_ray, e.g. to select the object that was clicked. The ray leaves from the center
+// of the lens so the answer doesn't change from one call to the next. rec.t is the distance
+// along the ray's (unnormalized) direction, rec.object_id the ID set by WithId.
+pub fn pick(world: &dyn Hittable, camera: &Camera, u: f64, v: f64) -> Option<HitRecord> {
+    let r = camera.get_ray_with_lens_sample(u, v, 0.5, 0.5);
+    world.hit2(r, T_MIN, f64::INFINITY)
+}
+
+// pick for the pixel in column x and row y of the full frame, counting rows from the top
+pub fn pick_pixel(
+    world: &dyn Hittable,
+    camera: &Camera,
+    settings: &RenderSettings,
+    x: i64,
+    y: i64,
+) -> Option<HitRecord> {
+    let draw_height = settings.image_height() - 1 - y;
+    let u = (x as f64 + 0.5) / (settings.image_width as f64 - 1.0);
+    let v = (draw_height as f64 + 0.5) / (settings.image_height() as f64 - 1.0);
+    pick(world, camera, u, v)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::Dielectric;
+    use crate::sphere::Sphere;
+
+    // Small enough that validate's size checks never get in the way
+    fn small_settings() -> RenderSettings {
+        RenderSettings {
+            image_width: 16,
+            fixed_height: Some(9),
+            samples_per_pixel: 4,
+            ..RenderSettings::default()
+        }
+    }
+
+    #[test]
+    fn zero_samples_per_pixel_is_rejected() {
+        assert!(small_settings().validate().is_ok());
+
+        let settings = RenderSettings {
+            samples_per_pixel: 0,
+            ..small_settings()
+        };
+        assert!(matches!(settings.validate(), Err(Error::InvalidConfig(_))));
+
+        let mut settings = small_settings();
+        let args = ["--samples", "0"].map(String::from);
+        assert!(matches!(
+            settings.apply_args(args),
+            Err(Error::InvalidConfig(_))
+        ));
+    }
+
+    #[test]
+    fn coverage_without_extra_samples_is_rejected() {
+        let mut settings = small_settings();
+        settings.coverage = Some(CoverageSettings {
+            extra_samples: 0,
+            ..Default::default()
+        });
+        assert!(settings.validate().is_err());
+
+        settings.coverage = Some(CoverageSettings::default());
+        assert!(settings.validate().is_ok());
+    }
+
+    #[test]
+    fn absorbing_glass_tints_a_solid_background() {
+        // Two units of glass that lets through 20% red, 40% green and all of the blue per unit
+        let glass = Dielectric::new(1.5).with_absorption(Color::new(0.2, 0.4, 1.0), 1.0);
+        let mut world = HittableList::new();
+        world.add(Arc::new(Sphere::new(Vec3::default(), 1.0, Arc::new(glass))));
+        let settings = RenderSettings {
+            background: Background::Solid(Color::new(1.0, 1.0, 1.0)),
+            ..small_settings()
+        };
+
+        seed_random(3);
+        let r = Ray::new(Vec3::new(0.0, 0.0, 5.0), Vec3::new(0.0, 0.0, -1.0));
+        let count = 2000;
+        let mut total = Color::default();
+        for _ in 0..count {
+            total += ray_color(r, &world, &settings);
+        }
+        let mean = total / count as f64;
+
+        // Head on almost everything goes straight through, with red down to 0.2^2 and green to
+        // 0.4^2. The few percent reflected off the front stay white.
+        assert!(mean.z > 0.95, "blue {}", mean.z);
+        assert!(mean.x < 0.15, "red {}", mean.x);
+        assert!(mean.y > mean.x && mean.y < 0.25, "green {}", mean.y);
+    }
+
+    #[test]
+    fn many_dim_samples_sum_without_drift() {
+        // An empty world under a dim solid sky, so every sample is the same small value. An f32
+        // sum of this many would already have drifted.
+        let sky = Color::new(1e-3, 2e-3, 3e-3);
+        let settings = RenderSettings {
+            background: Background::Solid(sky),
+            ..small_settings()
+        };
+        let cam = Camera::new(
+            Vec3::new(0.0, 0.0, 1.0),
+            Vec3::default(),
+            Vec3::new(0.0, 1.0, 0.0),
+            40.0,
+            settings.pixel_aspect_ratio(),
+            0.0,
+            1.0,
+        );
+
+        seed_random(5);
+        let samples = 200_000;
+        let (sum, weight) =
+            sample_pixel(&HittableList::new(), &cam, &settings, 8, 4, samples, None);
+        let mean = sum / weight;
+        for (got, want) in [(mean.x, sky.x), (mean.y, sky.y), (mean.z, sky.z)] {
+            assert!(
+                ((got - want) / want).abs() < 1e-9,
+                "{} instead of {}",
+                got,
+                want
+            );
+        }
+    }
+
+    #[test]
+    fn threads_argument_sets_the_thread_count() {
+        let mut settings = small_settings();
+        settings
+            .apply_args(["--threads", "3"].map(String::from))
+            .unwrap();
+        assert_eq!(settings.threads, 3);
+
+        let args = ["--threads=0"].map(String::from);
+        assert!(small_settings().apply_args(args).is_err());
+    }
+
+    #[test]
+    fn crop_argument_takes_four_values() {
+        let mut settings = small_settings();
+        let args = ["--crop", "2", "1", "10", "8", "--samples", "2"].map(String::from);
+        settings.apply_args(args).unwrap();
+        assert!(settings.crop == Some(Crop::new(2, 1, 10, 8)));
+        assert_eq!(settings.samples_per_pixel, 2);
+
+        let mut settings = small_settings();
+        settings
+            .apply_args(["--crop=0,0,4,4"].map(String::from))
+            .unwrap();
+        assert!(settings.crop == Some(Crop::new(0, 0, 4, 4)));
+
+        // Left as given, main clamps it into the frame with a warning
+        let mut settings = small_settings();
+        let args = ["--crop", "0", "0", "99", "99"].map(String::from);
+        settings.apply_args(args).unwrap();
+        assert!(settings.clamp_crop() == Some(Crop::new(0, 0, 99, 99)));
+        assert!(settings.crop == Some(Crop::new(0, 0, 16, 9)));
+
+        let args = ["--crop", "0", "0", "4"].map(String::from);
+        assert!(small_settings().apply_args(args).is_err());
+        let args = ["--crop", "0", "0", "4", "x"].map(String::from);
+        assert!(small_settings().apply_args(args).is_err());
+    }
+
+    #[test]
+    fn seeded_render_is_the_same_on_any_thread_count() {
+        let mut world = HittableList::new();
+        let glass = Arc::new(Dielectric::new(1.5));
+        world.add(Arc::new(Sphere::new(Vec3::new(0.0, 0.0, -1.0), 0.5, glass)));
+        let cam = Camera::new(
+            Vec3::default(),
+            Vec3::new(0.0, 0.0, -1.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            60.0,
+            16.0 / 9.0,
+            0.0,
+            1.0,
+        );
+        // Zero thresholds send every pixel through the later passes, which run on the calling
+        // thread and have to reseed it themselves
+        let plain = small_settings();
+        let with_coverage = RenderSettings {
+            coverage: Some(CoverageSettings {
+                threshold: 0.0,
+                extra_samples: 2,
+            }),
+            ..small_settings()
+        };
+        let with_edge_preview = RenderSettings {
+            edge_preview: Some(EdgePreviewSettings {
+                luminance_threshold: 0.0,
+                normal_threshold: 0.0,
+                edge_samples: 2,
+            }),
+            ..small_settings()
+        };
+
+        for base in [plain, with_coverage, with_edge_preview] {
+            let image = |threads: usize| {
+                let settings = RenderSettings {
+                    seed: Some(9),
+                    threads,
+                    ..base.clone()
+                };
+                render(&world, &cam, &settings)
+            };
+
+            let (one, four) = (image(1), image(4));
+            assert_eq!(one.len(), four.len());
+            for (a, b) in one.iter().zip(four.iter()) {
+                assert!(a.x == b.x && a.y == b.y && a.z == b.z);
+            }
+        }
+    }
+}
@@ -0,0 +1,198 @@
+use crate::hittable::HittableList;
+use crate::material::{Dielectric, Lambertian, Material, Metal};
+use crate::sphere::Sphere;
+use crate::utility::{degrees_to_radians, random_float, random_float_range};
+use crate::vec3::{cross, Color, Point3, Vec3};
+use std::sync::Arc;
+
+pub fn random_scene() -> HittableList {
+    let mut world = HittableList::new();
+
+    add_ground(&mut world);
+
+    for a in -11..11 {
+        for b in -11..11 {
+            let choose_mat = random_float();
+            let center = Point3::new(
+                a as f64 + 0.9 * random_float(),
+                0.2,
+                b as f64 + 0.9 * random_float(),
+            );
+
+            if (center - Point3::new(4.0, 0.2, 0.0)).length() > 0.9 {
+                world.add(Arc::new(Sphere::new(
+                    center,
+                    0.2,
+                    random_material(choose_mat),
+                )));
+            }
+        }
+    }
+
+    add_centerpieces(&mut world);
+
+    world
+}
+
+// Picks the material for one of the small spheres, choose_mat is uniform in [0, 1)
+fn random_material(choose_mat: f64) -> Arc<dyn Material> {
+    if choose_mat < 0.8 {
+        // Diffuse
+        let albedo = Color::random() * Color::random();
+        Arc::new(Lambertian::new(albedo))
+    } else if choose_mat < 0.95 {
+        // Metal
+        let albedo = Color::random_range(0.5, 1.0);
+        let fuzz = random_float_range(0.0, 0.5);
+        Arc::new(Metal::new(albedo, fuzz))
+    } else {
+        // Glass
+        Arc::new(Dielectric::new(1.5))
+    }
+}
+
+// The three big glass, diffuse and metal spheres in the middle of the sphere field
+fn add_centerpieces(world: &mut HittableList) {
+    let material1 = Arc::new(Dielectric::new(1.5));
+    world.add(Arc::new(Sphere::new(
+        Point3::new(0.0, 1.0, 0.0),
+        1.0,
+        material1,
+    )));
+
+    let material2 = Arc::new(Lambertian::new(Color::new(0.4, 0.2, 0.1)));
+    world.add(Arc::new(Sphere::new(
+        Point3::new(-4.0, 1.0, 0.0),
+        1.0,
+        material2,
+    )));
+
+    let material3 = Arc::new(Metal::new(Color::new(0.7, 0.6, 0.5), 0.0));
+    world.add(Arc::new(Sphere::new(
+        Point3::new(4.0, 1.0, 0.0),
+        1.0,
+        material3,
+    )));
+}
+
+fn add_ground(world: &mut HittableList) {
+    let ground_material = Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+    world.add(Arc::new(Sphere::new(
+        Point3::new(0.0, -1000.0, 0.0),
+        1000.0,
+        ground_material,
+    )));
+}
+
+// Recursive sphereflake fractal: every sphere sprouts nine spheres a third of its size, six
+// around its equator and three above, all facing away from their parent. Each level multiplies
+// the sphere count by nine, so depth 4 already gives 7381 spheres.
+pub fn sphereflake_scene(depth: u32) -> HittableList {
+    let mut world = HittableList::new();
+    add_ground(&mut world);
+
+    add_sphereflake(
+        &mut world,
+        Point3::new(0.0, 1.0, 0.0),
+        1.0,
+        Vec3::new(0.0, 1.0, 0.0),
+        depth,
+    );
+
+    world
+}
+
+fn add_sphereflake(world: &mut HittableList, center: Point3, radius: f64, axis: Vec3, depth: u32) {
+    let tint = 0.5 + 0.1 * (depth % 5) as f64;
+    let material = Arc::new(Metal::new(Color::new(tint, 0.6, 1.0 - tint * 0.5), 0.05));
+    world.add(Arc::new(Sphere::new(center, radius, material)));
+
+    if depth == 0 {
+        return;
+    }
+
+    let helper = if axis.x.abs() > 0.9 {
+        Vec3::new(0.0, 1.0, 0.0)
+    } else {
+        Vec3::new(1.0, 0.0, 0.0)
+    };
+    let tangent = Vec3::unit_vector(cross(helper, axis));
+    let bitangent = cross(axis, tangent);
+
+    let child_radius = radius / 3.0;
+    let ring = (0..6).map(|k| (0.0, k as f64 * 60.0));
+    let crown = (0..3).map(|k| (60.0, 30.0 + k as f64 * 120.0));
+
+    for (elevation, azimuth) in ring.chain(crown) {
+        let elevation = degrees_to_radians(elevation);
+        let azimuth = degrees_to_radians(azimuth);
+        let direction = (tangent * azimuth.cos() + bitangent * azimuth.sin()) * elevation.cos()
+            + axis * elevation.sin();
+
+        add_sphereflake(
+            world,
+            center + direction * (radius + child_radius),
+            child_radius,
+            direction,
+            depth - 1,
+        );
+    }
+}
+
+// Like random_scene, but the small spheres are scattered with Poisson-disk dart throwing rather
+// than jittered on a grid, so no two are closer than a minimum spacing and no rows show up.
+// Gives up early if `count` spheres don't fit after a while.
+pub fn poisson_scene(count: usize) -> HittableList {
+    let mut world = HittableList::new();
+    add_ground(&mut world);
+
+    let extent = 11.0;
+    let min_distance = 0.75 * ((2.0 * extent) * (2.0 * extent) / count.max(1) as f64).sqrt();
+    let min_distance = min_distance.max(0.45);
+    let keep_clear = [
+        Point3::new(0.0, 0.2, 0.0),
+        Point3::new(-4.0, 0.2, 0.0),
+        Point3::new(4.0, 0.2, 0.0),
+    ];
+
+    let mut centers: Vec<Point3> = Vec::with_capacity(count);
+    let mut attempts = 0;
+
+    while centers.len() < count && attempts < 30 * count {
+        attempts += 1;
+        let center = Point3::new(
+            random_float_range(-extent, extent),
+            0.2,
+            random_float_range(-extent, extent),
+        );
+
+        let overlaps_centerpiece = keep_clear.iter().any(|&c| (center - c).length() < 1.2);
+        let too_close = centers
+            .iter()
+            .any(|&other| (center - other).length() < min_distance);
+        if overlaps_centerpiece || too_close {
+            continue;
+        }
+
+        centers.push(center);
+        world.add(Arc::new(Sphere::new(
+            center,
+            0.2,
+            random_material(random_float()),
+        )));
+    }
+
+    add_centerpieces(&mut world);
+
+    world
+}
+
+// Looks up one of the built-in scenes by name
+pub fn scene_by_name(name: &str) -> Option<HittableList> {
+    match name {
+        "random" => Some(random_scene()),
+        "sphereflake" => Some(sphereflake_scene(4)),
+        "poisson" => Some(poisson_scene(500)),
+        _ => None,
+    }
+}
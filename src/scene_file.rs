@@ -0,0 +1,244 @@
+use crate::camera::Camera;
+use crate::error::Error;
+use crate::hittable::HittableList;
+use crate::json::{self, Json};
+use crate::material::{Dielectric, DiffuseLight, Lambertian, Material, Metal};
+use crate::render::Background;
+use crate::sphere::Sphere;
+use crate::vec3::{Point3, Vec3};
+use std::fs;
+use std::io;
+use std::sync::Arc;
+
+// A scene described in a JSON file, a camera and a list of objects:
+//
+//   {
+//     "camera": { "lookfrom": [13, 2, 3], "lookat": [0, 0, 0], "vfov": 20, "aperture": 0.1 },
+//     "objects": [
+//       { "type": "sphere", "center": [0, -1000, 0], "radius": 1000,
+//         "material": { "type": "lambertian", "albedo": [0.5, 0.5, 0.5] } },
+//       { "type": "sphere", "center": [0, 1, 0], "radius": 1,
+//         "material": { "type": "dielectric", "ior": 1.5 } }
+//     ]
+//   }
+//
+// An optional "background": [r, g, b] replaces the sky with that color, e.g. black for a room
+// lit only by diffuse_light objects.
+//
+// The camera takes lookfrom and lookat, plus optional vup (default [0, 1, 0]), vfov in
+// degrees (40), aperture (0) and focus_dist (the distance to lookat). Materials are
+// "lambertian" (albedo), "metal" (albedo, fuzz), "dielectric" (ior) and "diffuse_light" (emit).
+
+pub struct Scene {
+    pub world: HittableList,
+    pub camera: Camera,
+    // Background::Solid when the file sets one, Background::Gradient otherwise
+    pub background: Background,
+}
+
+impl Scene {
+    pub fn load(path: &str, aspect_ratio: f64) -> Result<Scene, Error> {
+        let text = fs::read_to_string(path).map_err(|err| {
+            Error::Io(io::Error::new(
+                err.kind(),
+                format!("Couldn't read '{}': {}", path, err),
+            ))
+        })?;
+
+        Scene::parse(&text, aspect_ratio).map_err(|err| match err {
+            Error::SceneLoad(message) | Error::InvalidConfig(message) => {
+                Error::SceneLoad(format!("Couldn't load '{}': {}", path, message))
+            }
+            other => other,
+        })
+    }
+
+    // Same as load, for JSON text that is already in memory
+    pub fn parse(text: &str, aspect_ratio: f64) -> Result<Scene, Error> {
+        let document = json::parse(text).map_err(Error::SceneLoad)?;
+
+        let camera = match document.get("camera") {
+            Some(camera) => parse_camera(camera, aspect_ratio)?,
+            None => return Err(Error::SceneLoad(String::from("the scene has no camera"))),
+        };
+
+        let background = match optional(&document, "background", vector) {
+            Ok(Some(color)) => Background::Solid(color),
+            Ok(None) => Background::Gradient,
+            Err(message) => return Err(Error::SceneLoad(message)),
+        };
+
+        let objects = match document.get("objects") {
+            Some(Json::Array(objects)) => objects.as_slice(),
+            Some(other) => {
+                return Err(Error::SceneLoad(format!(
+                    "objects must be an array, got {}",
+                    other.kind()
+                )))
+            }
+            None => &[],
+        };
+
+        let mut world = HittableList::new();
+        for (index, object) in objects.iter().enumerate() {
+            let sphere = parse_object(object)
+                .map_err(|message| Error::SceneLoad(format!("object {}: {}", index, message)))?;
+            world.add(Arc::new(sphere));
+        }
+
+        Ok(Scene {
+            world,
+            camera,
+            background,
+        })
+    }
+}
+
+fn parse_camera(camera: &Json, aspect_ratio: f64) -> Result<Camera, Error> {
+    let in_camera = |message| Error::SceneLoad(format!("camera: {}", message));
+    let lookfrom = vector(camera, "lookfrom").map_err(in_camera)?;
+    let lookat = vector(camera, "lookat").map_err(in_camera)?;
+    let vup = optional(camera, "vup", vector)
+        .map_err(in_camera)?
+        .unwrap_or(Vec3::new(0.0, 1.0, 0.0));
+    let vfov = optional(camera, "vfov", number)
+        .map_err(in_camera)?
+        .unwrap_or(40.0);
+    let aperture = optional(camera, "aperture", number)
+        .map_err(in_camera)?
+        .unwrap_or(0.0);
+    let focus_dist = optional(camera, "focus_dist", number).map_err(in_camera)?;
+    // A zero focus distance collapses the viewport onto the lens
+    if let Some(focus_dist) = focus_dist.filter(|&focus_dist| focus_dist <= 0.0) {
+        return Err(in_camera(format!(
+            "'focus_dist' must be positive, got {}",
+            focus_dist
+        )));
+    }
+
+    // Its messages already say they are about the camera
+    Camera::check_view(lookfrom, lookat, vup)?;
+    let camera = Camera::new(
+        lookfrom,
+        lookat,
+        vup,
+        vfov,
+        aspect_ratio,
+        aperture,
+        focus_dist.unwrap_or(1.0),
+    );
+    Ok(camera.with_autofocus(focus_dist.is_none()))
+}
+
+fn parse_object(object: &Json) -> Result<Sphere, String> {
+    match string(object, "type")?.as_str() {
+        "sphere" => {
+            let center: Point3 = vector(object, "center")?;
+            let radius = number(object, "radius")?;
+            let material = match object.get("material") {
+                Some(material) => parse_material(material)?,
+                None => return Err(String::from("missing 'material'")),
+            };
+            Ok(Sphere::new(center, radius, material))
+        }
+        other => Err(format!(
+            "unknown object type '{}', expected 'sphere'",
+            other
+        )),
+    }
+}
+
+fn parse_material(material: &Json) -> Result<Arc<dyn Material>, String> {
+    match string(material, "type")?.as_str() {
+        "lambertian" => Ok(Arc::new(Lambertian::new(vector(material, "albedo")?))),
+        "metal" => {
+            let fuzz = optional(material, "fuzz", number)?.unwrap_or(0.0);
+            Ok(Arc::new(Metal::new(vector(material, "albedo")?, fuzz)))
+        }
+        "dielectric" => Ok(Arc::new(Dielectric::new(number(material, "ior")?))),
+        "diffuse_light" => Ok(Arc::new(DiffuseLight::new(vector(material, "emit")?))),
+        other => Err(format!(
+            "unknown material type '{}', expected 'lambertian', 'metal', 'dielectric' or \
+             'diffuse_light'",
+            other
+        )),
+    }
+}
+
+// Field readers, each failing with a message naming the field
+
+fn field<'a>(object: &'a Json, key: &str) -> Result<&'a Json, String> {
+    object.get(key).ok_or_else(|| format!("missing '{}'", key))
+}
+
+fn optional<T>(
+    object: &Json,
+    key: &str,
+    read: fn(&Json, &str) -> Result<T, String>,
+) -> Result<Option<T>, String> {
+    match object.get(key) {
+        Some(_) => read(object, key).map(Some),
+        None => Ok(None),
+    }
+}
+
+fn number(object: &Json, key: &str) -> Result<f64, String> {
+    match field(object, key)? {
+        Json::Number(value) => Ok(*value),
+        other => Err(format!("'{}' must be a number, got {}", key, other.kind())),
+    }
+}
+
+fn string(object: &Json, key: &str) -> Result<String, String> {
+    match field(object, key)? {
+        Json::String(value) => Ok(value.clone()),
+        other => Err(format!("'{}' must be a string, got {}", key, other.kind())),
+    }
+}
+
+fn vector(object: &Json, key: &str) -> Result<Vec3, String> {
+    match field(object, key)? {
+        Json::Array(items) if items.len() == 3 => {
+            let mut values = [0.0; 3];
+            for (value, item) in values.iter_mut().zip(items) {
+                match item {
+                    Json::Number(number) => *value = *number,
+                    other => {
+                        return Err(format!(
+                            "'{}' must hold three numbers, got {}",
+                            key,
+                            other.kind()
+                        ))
+                    }
+                }
+            }
+            Ok(Vec3::new(values[0], values[1], values[2]))
+        }
+        _ => Err(format!("'{}' must be an array of three numbers", key)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn non_positive_focus_dist_is_rejected() {
+        let scene = |focus_dist: &str| {
+            format!(
+                "{{ \"camera\": {{ \"lookfrom\": [0, 0, 5], \"lookat\": [0, 0, 0], \
+                 \"focus_dist\": {} }} }}",
+                focus_dist
+            )
+        };
+        for focus_dist in ["0", "-2"] {
+            match Scene::parse(&scene(focus_dist), 1.0) {
+                Err(Error::SceneLoad(message)) => {
+                    assert!(message.contains("camera: 'focus_dist'"), "{}", message)
+                }
+                _ => panic!("focus_dist {} was accepted", focus_dist),
+            }
+        }
+        assert!(Scene::parse(&scene("4.5"), 1.0).is_ok());
+    }
+}
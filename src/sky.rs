@@ -0,0 +1,158 @@
+use crate::spectral::xyz_to_rgb;
+use crate::utility::degrees_to_radians;
+use crate::vec3::{dot, Color, Vec3};
+use std::f64::consts::PI;
+
+// Daylight sky from Preetham, Shirley and Smits, "A Practical Analytic Model for Daylight"
+// (1999). The sky's brightness and color follow the sun's position and the turbidity (haze,
+// 2 is a very clear day, 6 a hazy one), with a bright sun disk on top. Radiance is in
+// kilocandela per square metre times `scale`, which brings a clear midday sky to around 1.
+
+#[derive(Copy, Clone)]
+pub struct PhysicalSky {
+    sun_direction: Vec3,
+    turbidity: f64,
+    // Multiplies the sky and sun radiance
+    scale: f64,
+    sun_angular_radius: f64,
+    // Light arriving from the whole sun disk on a surface facing it, before the scale
+    sun_irradiance: f64,
+}
+
+impl PhysicalSky {
+    // `sun_direction` points from the scene towards the sun
+    pub fn new(sun_direction: Vec3, turbidity: f64) -> PhysicalSky {
+        PhysicalSky {
+            sun_direction: Vec3::unit_vector(sun_direction),
+            turbidity: turbidity.clamp(1.7, 10.0),
+            scale: 0.05,
+            sun_angular_radius: degrees_to_radians(0.53 / 2.0),
+            sun_irradiance: 40.0,
+        }
+    }
+
+    pub fn with_scale(mut self, scale: f64) -> PhysicalSky {
+        self.scale = scale;
+        self
+    }
+
+    // Apparent diameter of the sun in degrees, 0.53 for the real one. A bigger sun gives
+    // softer shadows and far fewer fireflies, since paths find it more often. The total light
+    // from the disk stays the same.
+    pub fn with_sun_size(mut self, degrees: f64) -> PhysicalSky {
+        self.sun_angular_radius = degrees_to_radians(degrees.max(0.0) / 2.0);
+        self
+    }
+
+    // How much light the sun disk adds compared to the sky, 0 leaves just the sky
+    pub fn with_sun_irradiance(mut self, irradiance: f64) -> PhysicalSky {
+        self.sun_irradiance = irradiance.max(0.0);
+        self
+    }
+
+    pub fn sun_direction(&self) -> Vec3 {
+        self.sun_direction
+    }
+
+    // Radiance seen along `direction`, with `up` pointing at the zenith. Below the horizon
+    // the sky is held at its horizon value.
+    pub fn value(&self, direction: Vec3, up: Vec3) -> Color {
+        let unit_direction = Vec3::unit_vector(direction);
+        let cos_theta = dot(unit_direction, up).max(0.001);
+        let cos_gamma = dot(unit_direction, self.sun_direction).clamp(-1.0, 1.0);
+        let gamma = cos_gamma.acos();
+        let sun_theta = dot(self.sun_direction, up)
+            .clamp(-1.0, 1.0)
+            .acos()
+            .min(PI / 2.0);
+
+        let sky = self.sky_radiance(cos_theta, gamma, sun_theta);
+        if gamma < self.sun_angular_radius && dot(self.sun_direction, up) > 0.0 {
+            let solid_angle = 2.0 * PI * (1.0 - self.sun_angular_radius.cos());
+            let sun =
+                sun_transmittance(sun_theta, self.turbidity) * (self.sun_irradiance / solid_angle);
+            return sky + sun * self.scale;
+        }
+        sky
+    }
+
+    fn sky_radiance(&self, cos_theta: f64, gamma: f64, sun_theta: f64) -> Color {
+        let t = self.turbidity;
+
+        // Zenith luminance and chromaticity
+        let chi = (4.0 / 9.0 - t / 120.0) * (PI - 2.0 * sun_theta);
+        let zenith_luminance = ((4.0453 * t - 4.9710) * chi.tan() - 0.2155 * t + 2.4192).max(0.0);
+        let (s1, s2, s3) = (
+            sun_theta,
+            sun_theta * sun_theta,
+            sun_theta * sun_theta * sun_theta,
+        );
+        let zenith_x = t * t * (0.00166 * s3 - 0.00375 * s2 + 0.00209 * s1)
+            + t * (-0.02903 * s3 + 0.06377 * s2 - 0.03202 * s1 + 0.00394)
+            + (0.11693 * s3 - 0.21196 * s2 + 0.06052 * s1 + 0.25886);
+        let zenith_y = t * t * (0.00275 * s3 - 0.00610 * s2 + 0.00317 * s1)
+            + t * (-0.04214 * s3 + 0.08970 * s2 - 0.04153 * s1 + 0.00516)
+            + (0.15346 * s3 - 0.26756 * s2 + 0.06670 * s1 + 0.26688);
+
+        // Perez distribution coefficients for luminance and the two chromaticity channels
+        let luminance = [
+            0.1787 * t - 1.4630,
+            -0.3554 * t + 0.4275,
+            -0.0227 * t + 5.3251,
+            0.1206 * t - 2.5771,
+            -0.0670 * t + 0.3703,
+        ];
+        let x = [
+            -0.0193 * t - 0.2592,
+            -0.0665 * t + 0.0008,
+            -0.0004 * t + 0.2125,
+            -0.0641 * t - 0.8989,
+            -0.0033 * t + 0.0452,
+        ];
+        let y = [
+            -0.0167 * t - 0.2608,
+            -0.0950 * t + 0.0092,
+            -0.0079 * t + 0.2102,
+            -0.0441 * t - 1.6537,
+            -0.0109 * t + 0.0529,
+        ];
+
+        // Relative to the zenith, where theta = 0 and gamma is the sun's zenith angle
+        let relative = |c: [f64; 5]| perez(cos_theta, gamma, c) / perez(1.0, sun_theta, c);
+        let big_y = zenith_luminance * relative(luminance);
+        let small_x = zenith_x * relative(x);
+        let small_y = (zenith_y * relative(y)).max(1e-6);
+
+        let xyz = Vec3::new(
+            small_x / small_y * big_y,
+            big_y,
+            (1.0 - small_x - small_y) / small_y * big_y,
+        );
+        let rgb = xyz_to_rgb(xyz) * self.scale;
+        rgb.max(Color::default())
+    }
+}
+
+// Perez et al.'s all-weather sky luminance distribution
+fn perez(cos_theta: f64, gamma: f64, c: [f64; 5]) -> f64 {
+    let cos_gamma = gamma.cos();
+    (1.0 + c[0] * (c[1] / cos_theta).exp())
+        * (1.0 + c[2] * (c[3] * gamma).exp() + c[4] * cos_gamma * cos_gamma)
+}
+
+// Share of sunlight that makes it through the atmosphere in each channel, from Rayleigh and
+// aerosol optical depths at 650, 550 and 450nm scaled by the air mass along the sun's path.
+// Gives a white sun overhead that turns orange and red towards the horizon.
+fn sun_transmittance(sun_theta: f64, turbidity: f64) -> Color {
+    let zenith_degrees = sun_theta.to_degrees();
+    let air_mass =
+        1.0 / (sun_theta.cos() + 0.15 * (93.885 - zenith_degrees).max(0.01).powf(-1.253));
+    let beta = 0.04608 * turbidity - 0.04586;
+
+    let channel = |wavelength_um: f64| {
+        let rayleigh = 0.008735 * wavelength_um.powf(-4.08);
+        let aerosol = beta * wavelength_um.powf(-1.3);
+        (-air_mass * (rayleigh + aerosol)).exp()
+    };
+    Color::new(channel(0.65), channel(0.55), channel(0.45))
+}
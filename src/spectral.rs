@@ -0,0 +1,161 @@
+use crate::hittable::Hittable;
+use crate::ray::Ray;
+use crate::render::{background, sample_lights, RenderSettings, T_MIN};
+use crate::utility::with_rng;
+use crate::vec3::{Color, Vec3};
+use rand::Rng;
+use std::sync::OnceLock;
+
+// Spectral path tracing: every camera sample follows a few single wavelengths instead of an
+// RGB triple, so glass with a wavelength dependent IOR splits white light into a rainbow.
+// Colors given in RGB (albedos, emission, the sky) are turned into smooth spectra on the fly,
+// and the result is converted back through the CIE observer. A scene without dispersion comes
+// out close to the RGB renderer, only noisier. The round trip through a spectrum isn't exact,
+// so strongly saturated colors drift from their RGB values.
+
+pub const MIN_WAVELENGTH: f64 = 380.0;
+pub const MAX_WAVELENGTH: f64 = 780.0;
+
+#[derive(Copy, Clone)]
+pub struct SpectralSettings {
+    // Wavelengths traced for every camera sample, spread evenly over the visible range
+    pub wavelength_samples: i64,
+}
+
+impl Default for SpectralSettings {
+    fn default() -> SpectralSettings {
+        SpectralSettings {
+            wavelength_samples: 4,
+        }
+    }
+}
+
+// RGB to spectrum: blue below 480nm, green from 500 to 580nm and red above 600nm, blended
+// linearly in between. The three weights always add up to 1, so white and grays stay flat.
+pub fn rgb_to_spectrum(color: Color, wavelength: f64) -> f64 {
+    let blue = 1.0 - ((wavelength - 480.0) / 20.0).clamp(0.0, 1.0);
+    let red = ((wavelength - 580.0) / 20.0).clamp(0.0, 1.0);
+    let green = 1.0 - blue - red;
+    color.x * red + color.y * green + color.z * blue
+}
+
+// CIE 1931 2 degree color matching functions, using the multi-lobe Gaussian fit from Wyman,
+// Sloan and Shirley, "Simple Analytic Approximations to the CIE XYZ Color Matching Functions"
+// (2013)
+pub fn cie_xyz(wavelength: f64) -> Vec3 {
+    let g = |mu: f64, sigma_below: f64, sigma_above: f64| {
+        let sigma = if wavelength < mu {
+            sigma_below
+        } else {
+            sigma_above
+        };
+        let t = (wavelength - mu) / sigma;
+        (-0.5 * t * t).exp()
+    };
+
+    Vec3::new(
+        1.056 * g(599.8, 37.9, 31.0) + 0.362 * g(442.0, 16.0, 26.7) - 0.065 * g(501.1, 20.4, 26.2),
+        0.821 * g(568.8, 46.9, 40.5) + 0.286 * g(530.9, 16.3, 31.1),
+        1.217 * g(437.0, 11.8, 36.0) + 0.681 * g(459.0, 26.0, 13.8),
+    )
+}
+
+// XYZ to linear sRGB primaries
+pub fn xyz_to_rgb(xyz: Vec3) -> Color {
+    Color::new(
+        3.2406 * xyz.x - 1.5372 * xyz.y - 0.4986 * xyz.z,
+        -0.9689 * xyz.x + 1.8758 * xyz.y + 0.0415 * xyz.z,
+        0.0557 * xyz.x - 0.2040 * xyz.y + 1.0570 * xyz.z,
+    )
+}
+
+// RGB of a flat spectrum of 1, integrated at 1nm steps. Dividing by it white balances the
+// output so flat spectra come back as (1, 1, 1) rather than the pinkish equal-energy white.
+fn white_rgb() -> Color {
+    static WHITE: OnceLock<(f64, f64, f64)> = OnceLock::new();
+    let &(r, g, b) = WHITE.get_or_init(|| {
+        let mut xyz = Vec3::default();
+        let mut wavelength = MIN_WAVELENGTH + 0.5;
+        while wavelength < MAX_WAVELENGTH {
+            xyz += cie_xyz(wavelength);
+            wavelength += 1.0;
+        }
+        let rgb = xyz_to_rgb(xyz);
+        (rgb.x, rgb.y, rgb.z)
+    });
+    Color::new(r, g, b)
+}
+
+// Radiance arriving along `r` as RGB, estimated from settings.spectral's wavelength count.
+// The wavelengths are stratified across the visible range with one random offset.
+pub(crate) fn trace_spectral(
+    r: Ray,
+    world: &dyn Hittable,
+    settings: &RenderSettings,
+    spectral: &SpectralSettings,
+) -> Color {
+    let count = spectral.wavelength_samples.max(1);
+    let offset = with_rng(|rng| rng.gen::<f64>());
+    let range = MAX_WAVELENGTH - MIN_WAVELENGTH;
+
+    let mut xyz = Vec3::default();
+    for i in 0..count {
+        let wavelength = MIN_WAVELENGTH + range * (i as f64 + offset) / count as f64;
+        xyz += cie_xyz(wavelength) * trace_wavelength(r, world, settings, wavelength);
+    }
+
+    // Each wavelength stands for range / count nanometres, the white integral for 1nm each
+    let rgb = xyz_to_rgb(xyz * (range / count as f64));
+    let white = white_rgb();
+    Color::new(rgb.x / white.x, rgb.y / white.y, rgb.z / white.z)
+}
+
+fn trace_wavelength(
+    r: Ray,
+    world: &dyn Hittable,
+    settings: &RenderSettings,
+    wavelength: f64,
+) -> f64 {
+    let mut ray = r;
+    let mut throughput = 1.0;
+    let mut radiance = 0.0;
+
+    for _ in 0..settings.max_depth {
+        let rec = match world.hit2(ray, T_MIN, f64::INFINITY) {
+            Some(rec) => rec,
+            None => {
+                radiance += throughput * rgb_to_spectrum(background(ray, settings), wavelength);
+                break;
+            }
+        };
+
+        radiance += throughput * rgb_to_spectrum(rec.mat_ptr.emitted(&rec), wavelength);
+
+        let mut scattered = Ray::default();
+        let mut attenuation = 0.0;
+        let scatters = with_rng(|rng| {
+            rec.mat_ptr.scatter_spectral(
+                ray,
+                rec.clone(),
+                wavelength,
+                &mut attenuation,
+                &mut scattered,
+                rng,
+            )
+        });
+        if !scatters {
+            break;
+        }
+        if let Some(weight) = sample_lights(ray, &rec, &mut scattered, settings) {
+            attenuation = rgb_to_spectrum(weight, wavelength);
+        }
+
+        throughput *= attenuation;
+        if throughput < settings.min_throughput {
+            break;
+        }
+        ray = scattered;
+    }
+
+    radiance
+}
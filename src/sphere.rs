@@ -0,0 +1,174 @@
+use crate::aabb::Aabb;
+use crate::hittable::{valid_interval, HitRecord, Hittable};
+use crate::material::Material;
+use crate::onb::Onb;
+use crate::ray::Ray;
+use crate::render::T_MIN;
+use crate::vec3::{dot, Point3, Vec3};
+use rand::{Rng, RngCore};
+use std::any::Any;
+use std::f64::consts::PI;
+use std::sync::Arc;
+
+pub struct Sphere {
+    center: Point3,
+    radius: f64,
+    mat_ptr: Arc<dyn Material>,
+}
+
+impl Sphere {
+    pub fn new(center: Point3, radius: f64, material: Arc<dyn Material>) -> Sphere {
+        Sphere {
+            center,
+            radius,
+            mat_ptr: material,
+        }
+    }
+
+    pub fn center(&self) -> Point3 {
+        self.center
+    }
+
+    pub fn radius(&self) -> f64 {
+        self.radius
+    }
+
+    pub fn material(&self) -> &Arc<dyn Material> {
+        &self.mat_ptr
+    }
+
+    pub fn set_center(&mut self, center: Point3) {
+        self.center = center;
+    }
+
+    pub fn set_radius(&mut self, radius: f64) {
+        self.radius = radius;
+    }
+
+    pub fn set_material(&mut self, material: Arc<dyn Material>) {
+        self.mat_ptr = material;
+    }
+
+    // Distance along `r` to the closest surface crossing within [t_min, t_max]
+    fn root(&self, r: Ray, t_min: f64, t_max: f64) -> Option<f64> {
+        // An inverted (or NaN) interval can't contain any root
+        if !valid_interval(t_min, t_max) {
+            return None;
+        }
+
+        let oc = r.origin - self.center;
+        let a = r.direction.length_squared();
+        let half_b = dot(oc, r.direction);
+        let c = oc.length_squared() - self.radius * self.radius;
+
+        let discriminant = half_b * half_b - a * c;
+        if discriminant < 0.0 {
+            return None;
+        }
+        let sqrtd = discriminant.sqrt();
+
+        // Solve for both roots without subtracting two nearly equal numbers, which loses the
+        // small root for big spheres like the ground or for rays starting right on the surface
+        let q = if half_b > 0.0 {
+            -half_b - sqrtd
+        } else {
+            -half_b + sqrtd
+        };
+        let root1 = q / a;
+        let root2 = if q != 0.0 { c / q } else { root1 };
+        let (near, far) = if root1 <= root2 {
+            (root1, root2)
+        } else {
+            (root2, root1)
+        };
+
+        // Find the closest root that is within the acceptable range. When the ray starts inside
+        // the sphere the near root lies behind it, so the far root is the enclosing surface and
+        // set_face_normal reports it as a back face with the normal turned toward the ray.
+
+        let mut root = near;
+        if root < t_min || t_max < root {
+            root = far;
+            if root < t_min || t_max < root {
+                return None;
+            }
+        }
+        Some(root)
+    }
+}
+
+impl Hittable for Sphere {
+    fn hit(&self, r: Ray, t_min: f64, t_max: f64, rec: &mut HitRecord) -> bool {
+        let root = match self.root(r, t_min, t_max) {
+            Some(root) => root,
+            None => return false,
+        };
+
+        rec.t = root;
+        rec.p = r.at(rec.t);
+        let outward_normal = (rec.p - self.center) / self.radius;
+        rec.set_face_normal(r, outward_normal);
+        rec.mat_ptr = self.mat_ptr.clone();
+
+        true
+    }
+
+    fn hit_any(&self, r: Ray, t_min: f64, t_max: f64) -> bool {
+        self.root(r, t_min, t_max).is_some()
+    }
+
+    // The radius may be negative for the inside of a hollow glass ball
+    fn bounding_box(&self) -> Option<Aabb> {
+        let extent = Vec3::new(self.radius.abs(), self.radius.abs(), self.radius.abs());
+        Some(Aabb::new(self.center - extent, self.center + extent))
+    }
+
+    // Samples the cone of directions the sphere covers as seen from `origin`, so every sample
+    // hits it. From inside the sphere every direction hits, and directions are uniform over
+    // the whole sphere of directions instead.
+    fn pdf_value(&self, origin: Point3, direction: Vec3) -> f64 {
+        if self
+            .root(Ray::new(origin, direction), T_MIN, f64::INFINITY)
+            .is_none()
+        {
+            return 0.0;
+        }
+
+        let distance_squared = (self.center - origin).length_squared();
+        let radius_squared = self.radius * self.radius;
+        if distance_squared <= radius_squared {
+            return 1.0 / (4.0 * PI);
+        }
+
+        let cos_theta_max = (1.0 - radius_squared / distance_squared).sqrt();
+        let solid_angle = 2.0 * PI * (1.0 - cos_theta_max);
+        1.0 / solid_angle
+    }
+
+    fn random(&self, origin: Point3, rng: &mut dyn RngCore) -> Vec3 {
+        let direction = self.center - origin;
+        let distance_squared = direction.length_squared();
+        let radius_squared = self.radius * self.radius;
+        if distance_squared <= radius_squared {
+            return Vec3::random_unit_vector_from(rng);
+        }
+
+        let cos_theta_max = (1.0 - radius_squared / distance_squared).sqrt();
+        let z = 1.0 + rng.gen::<f64>() * (cos_theta_max - 1.0);
+        let phi = 2.0 * PI * rng.gen::<f64>();
+        let sin_theta = (1.0 - z * z).max(0.0).sqrt();
+        Onb::build_from_w(direction).local(sin_theta * phi.cos(), sin_theta * phi.sin(), z)
+    }
+
+    fn clone_box(&self) -> Arc<dyn Hittable> {
+        Arc::new(Sphere::new(
+            self.center,
+            self.radius,
+            self.mat_ptr.clone_box(),
+        ))
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
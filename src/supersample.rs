@@ -0,0 +1,67 @@
+use crate::render::{Crop, RenderSettings};
+use crate::vec3::Vec3;
+
+// Supersampling renders the frame at `ssaa` times the resolution in each direction and then
+// averages every ssaa x ssaa block back into one output pixel. The averaging is done on the
+// linear sample sums, before write_color applies gamma.
+
+// The same settings, scaled up to the high resolution frame and with supersampling turned off
+pub(crate) fn upscaled_settings(settings: &RenderSettings) -> RenderSettings {
+    let factor = settings.ssaa;
+    let region = settings.region();
+
+    let mut upscaled = settings.clone();
+    upscaled.ssaa = 1;
+    upscaled.image_width = settings.image_width * factor;
+    upscaled.fixed_height = Some(settings.image_height() * factor);
+    if settings.crop.is_some() {
+        upscaled.crop = Some(Crop::new(
+            region.x0 * factor,
+            region.y0 * factor,
+            region.x1 * factor,
+            region.y1 * factor,
+        ));
+    }
+    upscaled
+}
+
+// Box filters a high resolution buffer down to the output size of `settings`
+pub(crate) fn downsample(pixels: &[Vec3], settings: &RenderSettings) -> Vec<Vec3> {
+    let factor = settings.ssaa;
+    let width = settings.region().width();
+    let height = settings.region().height();
+    let scale = 1.0 / (factor * factor) as f64;
+
+    let mut output = Vec::with_capacity((width * height) as usize);
+    for y in 0..height {
+        for x in 0..width {
+            let mut sum = Vec3::default();
+            for dy in 0..factor {
+                for dx in 0..factor {
+                    sum += pixels[high_res_index(x, y, dx, dy, width, factor)];
+                }
+            }
+            output.push(sum * scale);
+        }
+    }
+    output
+}
+
+// IDs can't be averaged, so each output pixel takes the ID of its top-left sub-pixel
+pub(crate) fn downsample_ids(ids: &[u32], settings: &RenderSettings) -> Vec<u32> {
+    let factor = settings.ssaa;
+    let width = settings.region().width();
+    let height = settings.region().height();
+
+    let mut output = Vec::with_capacity((width * height) as usize);
+    for y in 0..height {
+        for x in 0..width {
+            output.push(ids[high_res_index(x, y, 0, 0, width, factor)]);
+        }
+    }
+    output
+}
+
+fn high_res_index(x: i64, y: i64, dx: i64, dy: i64, width: i64, factor: i64) -> usize {
+    ((y * factor + dy) * width * factor + x * factor + dx) as usize
+}
@@ -0,0 +1,67 @@
+use crate::render::{encode_color, luminance, RenderSettings};
+use crate::utility::clamp;
+use crate::vec3::Color;
+
+// Auto-exposure scales the whole image so its log-average luminance lands on `target`, with
+// the scale factor kept inside [min_scale, max_scale]
+
+#[derive(Copy, Clone)]
+pub struct AutoExposure {
+    pub target: f64,
+    pub min_scale: f64,
+    pub max_scale: f64,
+}
+
+impl Default for AutoExposure {
+    fn default() -> AutoExposure {
+        AutoExposure {
+            target: 0.18,
+            min_scale: 1.0 / 64.0,
+            max_scale: 64.0,
+        }
+    }
+}
+
+// Exposure scale for a buffer of per-pixel sample sums. The log-average is used instead of the
+// plain mean so a few very bright pixels (like the sun) don't plunge everything else into black.
+pub fn exposure_scale(pixels: &[Color], samples_per_pixel: i64, exposure: &AutoExposure) -> f64 {
+    if pixels.is_empty() {
+        return 1.0;
+    }
+
+    // Keeps black pixels from sending the logarithm to -infinity
+    let delta = 1e-4;
+    let scale = 1.0 / samples_per_pixel as f64;
+    let log_sum: f64 = pixels
+        .iter()
+        .map(|&pixel| (delta + luminance(pixel * scale).max(0.0)).ln())
+        .sum();
+    let log_average = (log_sum / pixels.len() as f64).exp();
+
+    clamp(
+        exposure.target / log_average,
+        exposure.min_scale,
+        exposure.max_scale,
+    )
+}
+
+// Rescales the buffer in place and returns the scale that was applied
+pub fn apply_auto_exposure(
+    pixels: &mut [Color],
+    samples_per_pixel: i64,
+    exposure: &AutoExposure,
+) -> f64 {
+    let scale = exposure_scale(pixels, samples_per_pixel, exposure);
+    for pixel in pixels.iter_mut() {
+        *pixel *= scale;
+    }
+    scale
+}
+
+// Turns one pixel of a render buffer into the 8-bit RGB the built-in writers produce for it,
+// so custom sinks and windows match the .ppm output exactly. `samples` is what the pixel was
+// scaled to, normally settings.samples_per_pixel. Auto-exposure works on the image as a
+// whole, so run apply_auto_exposure over the buffer first when it is enabled.
+pub fn tonemap_and_encode(color: Color, samples: i64, settings: &RenderSettings) -> [u8; 3] {
+    encode_color(color, samples, settings.gamma)
+}
@@ -0,0 +1,109 @@
+use crate::aabb::Aabb;
+use crate::hittable::{valid_interval, HitRecord, Hittable};
+use crate::material::Material;
+use crate::ray::Ray;
+use crate::vec3::{cross, dot, Point3, Vec3};
+use std::any::Any;
+use std::sync::Arc;
+
+// Flat triangle. The vertices go counter-clockwise when seen from the front, which is the
+// side the outward normal points to, same as the faces of an OBJ file.
+
+pub struct Triangle {
+    v0: Point3,
+    v1: Point3,
+    v2: Point3,
+    mat_ptr: Arc<dyn Material>,
+}
+
+impl Triangle {
+    pub fn new(v0: Point3, v1: Point3, v2: Point3, material: Arc<dyn Material>) -> Triangle {
+        Triangle {
+            v0,
+            v1,
+            v2,
+            mat_ptr: material,
+        }
+    }
+
+    pub fn vertices(&self) -> [Point3; 3] {
+        [self.v0, self.v1, self.v2]
+    }
+
+    pub fn material(&self) -> &Arc<dyn Material> {
+        &self.mat_ptr
+    }
+
+    // Distance along `r` to the triangle within [t_min, t_max] (Möller-Trumbore)
+    fn root(&self, r: Ray, t_min: f64, t_max: f64) -> Option<f64> {
+        if !valid_interval(t_min, t_max) {
+            return None;
+        }
+
+        let edge1 = self.v1 - self.v0;
+        let edge2 = self.v2 - self.v0;
+        let p = cross(r.direction, edge2);
+        let det = dot(edge1, p);
+        // Rays in the plane of the triangle, and degenerate triangles, never hit
+        if det.abs() < 1e-12 {
+            return None;
+        }
+        let inv_det = 1.0 / det;
+
+        let s = r.origin - self.v0;
+        let u = dot(s, p) * inv_det;
+        if !(0.0..=1.0).contains(&u) {
+            return None;
+        }
+        let q = cross(s, edge1);
+        let v = dot(r.direction, q) * inv_det;
+        if v < 0.0 || u + v > 1.0 {
+            return None;
+        }
+
+        let t = dot(edge2, q) * inv_det;
+        if t < t_min || t_max < t {
+            return None;
+        }
+        Some(t)
+    }
+}
+
+impl Hittable for Triangle {
+    fn hit(&self, r: Ray, t_min: f64, t_max: f64, rec: &mut HitRecord) -> bool {
+        let root = match self.root(r, t_min, t_max) {
+            Some(root) => root,
+            None => return false,
+        };
+
+        rec.t = root;
+        rec.p = r.at(rec.t);
+        let outward_normal = Vec3::unit_vector(cross(self.v1 - self.v0, self.v2 - self.v0));
+        rec.set_face_normal(r, outward_normal);
+        rec.mat_ptr = self.mat_ptr.clone();
+
+        true
+    }
+
+    fn hit_any(&self, r: Ray, t_min: f64, t_max: f64) -> bool {
+        self.root(r, t_min, t_max).is_some()
+    }
+
+    // Flat along an axis for triangles lying in an axis plane, which the slab test handles
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(Aabb::new(self.v0, self.v1).surrounding(Aabb::new(self.v2, self.v2)))
+    }
+
+    fn clone_box(&self) -> Arc<dyn Hittable> {
+        Arc::new(Triangle::new(
+            self.v0,
+            self.v1,
+            self.v2,
+            self.mat_ptr.clone_box(),
+        ))
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
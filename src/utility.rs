@@ -0,0 +1,51 @@
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::cell::RefCell;
+
+// Utility functions
+
+// Clamp, keeps a value within a desired range
+pub fn clamp(x: f64, min: f64, max: f64) -> f64 {
+    if x < min {
+        return min;
+    }
+    if x > max {
+        max
+    } else {
+        x
+    }
+}
+
+thread_local! {
+    // Every random number the tracer uses comes from here, one generator per thread. Seeded
+    // renders reseed it at the start of every row, so it doesn't matter which thread renders
+    // which row. Until then it is seeded from the OS.
+    static RNG: RefCell<StdRng> = RefCell::new(StdRng::from_entropy());
+}
+
+// Restarts the current thread's generator from a fixed seed
+pub fn seed_random(seed: u64) {
+    RNG.with(|rng| *rng.borrow_mut() = StdRng::seed_from_u64(seed));
+}
+
+// Lends out the current thread's generator, e.g. to hand to Material::scatter. While `f` runs
+// the generator is borrowed, so random_float and friends must not be called from inside it.
+pub fn with_rng<T>(f: impl FnOnce(&mut StdRng) -> T) -> T {
+    RNG.with(|rng| f(&mut rng.borrow_mut()))
+}
+
+pub fn random_float() -> f64 {
+    with_rng(|rng| rng.gen::<f64>())
+}
+
+// Uniform in [min, max). An empty, infinite or NaN range gives back min rather than panicking.
+pub fn random_float_range(min: f64, max: f64) -> f64 {
+    if min >= max || !(max - min).is_finite() {
+        return min;
+    }
+    with_rng(|rng| rng.gen_range(min..max))
+}
+
+pub fn degrees_to_radians(degrees: f64) -> f64 {
+    degrees * std::f64::consts::PI / 180.0
+}
@@ -0,0 +1,375 @@
+use crate::utility::{random_float, random_float_range, with_rng};
+use rand::{Rng, RngCore};
+use std::ops::*;
+
+// Rejection samplers give up after this many draws and return a fixed point on the boundary,
+// so a broken generator can't hang a sample. A working one runs out of tries with odds of
+// roughly 1 in 10^32.
+const MAX_REJECTION_TRIES: usize = 100;
+
+#[derive(Copy, Clone)]
+pub struct Vec3 {
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
+}
+
+impl Default for Vec3 {
+    fn default() -> Vec3 {
+        Vec3 {
+            x: 0.0,
+            y: 0.0,
+            z: 0.0,
+        }
+    }
+}
+
+impl Neg for Vec3 {
+    type Output = Self;
+    fn neg(self) -> Self {
+        Self {
+            x: -self.x,
+            y: -self.y,
+            z: -self.z,
+        }
+    }
+}
+
+impl Add for Vec3 {
+    type Output = Self;
+    fn add(self, rhs: Self) -> Self {
+        Self {
+            x: self.x + rhs.x,
+            y: self.y + rhs.y,
+            z: self.z + rhs.z,
+        }
+    }
+}
+
+// If having problems with multiplying, remember original code had two definitions that were
+// redundant, but maybe actually necessary.
+
+impl Mul<f64> for Vec3 {
+    type Output = Self;
+    fn mul(self, t: f64) -> Self {
+        Self {
+            x: self.x * t,
+            y: self.y * t,
+            z: self.z * t,
+        }
+    }
+}
+
+impl Mul<Vec3> for f64 {
+    type Output = Vec3;
+    fn mul(self, rhs: Vec3) -> Vec3 {
+        Vec3 {
+            x: rhs.x * self,
+            y: rhs.y * self,
+            z: rhs.z * self,
+        }
+    }
+}
+
+impl Mul<Vec3> for Vec3 {
+    type Output = Self;
+    fn mul(self, rhs: Self) -> Self {
+        Self {
+            x: self.x * rhs.x,
+            y: self.y * rhs.y,
+            z: self.z * rhs.z,
+        }
+    }
+}
+
+impl Div<Vec3> for Vec3 {
+    type Output = Self;
+    fn div(self, rhs: Self) -> Self {
+        Self {
+            x: self.x / rhs.x,
+            y: self.y / rhs.y,
+            z: self.z / rhs.z,
+        }
+    }
+}
+
+impl Div<f64> for Vec3 {
+    type Output = Self;
+    fn div(self, t: f64) -> Self {
+        Self {
+            x: self.x / t,
+            y: self.y / t,
+            z: self.z / t,
+        }
+    }
+}
+
+impl Sub for Vec3 {
+    type Output = Self;
+    fn sub(self, rhs: Self) -> Self {
+        Self {
+            x: self.x - rhs.x,
+            y: self.y - rhs.y,
+            z: self.z - rhs.z,
+        }
+    }
+}
+
+impl AddAssign<Vec3> for Vec3 {
+    fn add_assign(&mut self, rhs: Self) {
+        *self = Self {
+            x: self.x + rhs.x,
+            y: self.y + rhs.y,
+            z: self.z + rhs.z,
+        }
+    }
+}
+
+impl MulAssign<f64> for Vec3 {
+    fn mul_assign(&mut self, t: f64) {
+        *self = Self {
+            x: self.x * t,
+            y: self.y * t,
+            z: self.z * t,
+        }
+    }
+}
+
+impl DivAssign<f64> for Vec3 {
+    fn div_assign(&mut self, t: f64) {
+        *self = Self {
+            x: self.x / t,
+            y: self.y / t,
+            z: self.z / t,
+        }
+    }
+}
+
+pub fn cross(u: Vec3, v: Vec3) -> Vec3 {
+    Vec3 {
+        x: u.y * v.z - u.z * v.y,
+        y: u.z * v.x - u.x * v.z,
+        z: u.x * v.y - u.y * v.x,
+    }
+}
+
+pub fn dot(u: Vec3, v: Vec3) -> f64 {
+    u.x * v.x + u.y * v.y + u.z * v.z
+}
+
+impl Vec3 {
+    pub fn new(x: f64, y: f64, z: f64) -> Vec3 {
+        Vec3 { x, y, z }
+    }
+
+    pub fn near_zero(&self) -> bool {
+        let s = 1e-8;
+        (self.x.abs() < s) && (self.y.abs() < s) && (self.z.abs() < s)
+    }
+
+    // Component-wise helpers, e.g. a.min(b) and a.max(b) for the corners of a bounding box
+    pub fn min(self, other: Vec3) -> Vec3 {
+        Vec3::new(
+            self.x.min(other.x),
+            self.y.min(other.y),
+            self.z.min(other.z),
+        )
+    }
+
+    pub fn max(self, other: Vec3) -> Vec3 {
+        Vec3::new(
+            self.x.max(other.x),
+            self.y.max(other.y),
+            self.z.max(other.z),
+        )
+    }
+
+    pub fn abs(self) -> Vec3 {
+        Vec3::new(self.x.abs(), self.y.abs(), self.z.abs())
+    }
+
+    // Like f64::clamp, so it panics if any component of min is above max
+    pub fn clamp(self, min: Vec3, max: Vec3) -> Vec3 {
+        Vec3::new(
+            self.x.clamp(min.x, max.x),
+            self.y.clamp(min.y, max.y),
+            self.z.clamp(min.z, max.z),
+        )
+    }
+
+    // Largest and smallest component
+    pub fn max_component(self) -> f64 {
+        self.x.max(self.y).max(self.z)
+    }
+
+    pub fn min_component(self) -> f64 {
+        self.x.min(self.y).min(self.z)
+    }
+
+    pub fn random() -> Vec3 {
+        Vec3 {
+            x: random_float(),
+            y: random_float(),
+            z: random_float(),
+        }
+    }
+
+    pub fn random_range(min: f64, max: f64) -> Vec3 {
+        Vec3 {
+            x: random_float_range(min, max),
+            y: random_float_range(min, max),
+            z: random_float_range(min, max),
+        }
+    }
+
+    pub fn random_in_unit_sphere() -> Vec3 {
+        with_rng(|rng| Self::random_in_unit_sphere_from(rng))
+    }
+
+    pub fn random_unit_vector() -> Vec3 {
+        with_rng(|rng| Self::random_unit_vector_from(rng))
+    }
+
+    // Same as random_in_unit_sphere and random_unit_vector, but drawing from `rng` instead of
+    // the thread's generator
+    pub fn random_in_unit_sphere_from(rng: &mut dyn RngCore) -> Vec3 {
+        for _ in 0..MAX_REJECTION_TRIES {
+            let p = Vec3::new(
+                rng.gen_range(-1.0..1.0),
+                rng.gen_range(-1.0..1.0),
+                rng.gen_range(-1.0..1.0),
+            );
+            if p.length_squared() >= 1.0 || p.near_zero() {
+                continue;
+            }
+            return p;
+        }
+        Vec3::new(0.0, 0.0, 1.0)
+    }
+
+    pub fn random_unit_vector_from(rng: &mut dyn RngCore) -> Vec3 {
+        Self::unit_vector(Self::random_in_unit_sphere_from(rng))
+    }
+
+    pub fn random_in_unit_disk() -> Vec3 {
+        for _ in 0..MAX_REJECTION_TRIES {
+            let p = Vec3::new(
+                random_float_range(-1.0, 1.0),
+                random_float_range(-1.0, 1.0),
+                0.0,
+            );
+            if p.length_squared() >= 1.0 {
+                continue;
+            } else {
+                return p;
+            }
+        }
+        Vec3::new(1.0, 0.0, 0.0)
+    }
+
+    // Maps (u1, u2) in [0, 1)^2 onto the unit disk (z = 0) without rejection, following
+    // Shirley and Chiu's concentric mapping. Squares around the center go to rings, so nearby
+    // and evenly spread inputs stay that way on the disk. random_in_unit_disk is still there
+    // for plain random points.
+    pub fn concentric_disk_sample(u1: f64, u2: f64) -> Vec3 {
+        let a = 2.0 * u1 - 1.0;
+        let b = 2.0 * u2 - 1.0;
+        if a == 0.0 && b == 0.0 {
+            return Vec3::default();
+        }
+
+        let quarter_pi = std::f64::consts::FRAC_PI_4;
+        let (radius, theta) = if a.abs() > b.abs() {
+            (a, quarter_pi * (b / a))
+        } else {
+            (b, 2.0 * quarter_pi - quarter_pi * (a / b))
+        };
+        Vec3::new(radius * theta.cos(), radius * theta.sin(), 0.0)
+    }
+
+    // Cosine-weighted direction on the +z hemisphere from (u1, u2) in [0, 1)^2, the concentric
+    // disk point lifted up onto the hemisphere. Same density as random_cosine_direction.
+    pub fn cosine_direction_from_sample(u1: f64, u2: f64) -> Vec3 {
+        let d = Vec3::concentric_disk_sample(u1, u2);
+        let z = (1.0 - d.x * d.x - d.y * d.y).max(0.0).sqrt();
+        Vec3::new(d.x, d.y, z)
+    }
+
+    // Direction on the +z hemisphere with density proportional to its z component (the cosine
+    // to the pole), which is what diffuse scattering and ambient occlusion draw from. Turn it
+    // into world space with Onb::local.
+    pub fn random_cosine_direction() -> Vec3 {
+        with_rng(|rng| Self::random_cosine_direction_from(rng))
+    }
+
+    pub fn random_cosine_direction_from(rng: &mut dyn RngCore) -> Vec3 {
+        let r1 = rng.gen::<f64>();
+        let r2 = rng.gen::<f64>();
+
+        // Uniform point on the unit disk, projected up onto the hemisphere (Malley's method)
+        let phi = 2.0 * std::f64::consts::PI * r1;
+        let radius = r2.sqrt();
+        Vec3::new(radius * phi.cos(), radius * phi.sin(), (1.0 - r2).sqrt())
+    }
+
+    pub fn unit_vector(vector: Vec3) -> Vec3 {
+        vector / vector.length()
+    }
+
+    pub fn length(self) -> f64 {
+        self.length_squared().sqrt()
+        // eprintln!("length value {}", x);
+        // x
+    }
+    pub fn length_squared(self) -> f64 {
+        (self.x * self.x) + (self.y * self.y) + (self.z * self.z)
+    }
+}
+
+pub type Point3 = Vec3;
+pub type Color = Vec3;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::mock::StepRng;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    #[test]
+    fn cosine_directions_average_two_thirds_up() {
+        let mut rng = StdRng::seed_from_u64(7);
+        let count = 100_000;
+        let mut total_z = 0.0;
+        for _ in 0..count {
+            let d = Vec3::random_cosine_direction_from(&mut rng);
+            assert!(d.z >= 0.0);
+            assert!((d.length() - 1.0).abs() < 1e-9);
+            total_z += d.z;
+        }
+
+        // The mean of cos(theta) under a cosine-weighted density is 2/3
+        let mean_z = total_z / count as f64;
+        assert!((mean_z - 2.0 / 3.0).abs() < 0.005, "mean z {}", mean_z);
+    }
+
+    #[test]
+    fn rejection_gives_up_on_a_stuck_generator() {
+        // Every draw comes out at the top of its range, so every candidate is rejected
+        let mut stuck = StepRng::new(u64::MAX, 0);
+        let p = Vec3::random_in_unit_sphere_from(&mut stuck);
+        assert_eq!((p.x, p.y, p.z), (0.0, 0.0, 1.0));
+
+        let v = Vec3::random_unit_vector_from(&mut stuck);
+        assert!((v.length() - 1.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn unit_sphere_points_are_inside() {
+        let mut rng = StdRng::seed_from_u64(11);
+        for _ in 0..1000 {
+            let p = Vec3::random_in_unit_sphere_from(&mut rng);
+            assert!(p.length_squared() < 1.0);
+        }
+    }
+}